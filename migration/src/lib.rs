@@ -1,8 +1,20 @@
 pub use sea_orm_migration::prelude::*;
 
 mod initialize_table;
+mod migration_account_hostname;
+mod migration_acme_challenge;
+mod migration_admin_disabled;
+mod migration_admin_role;
+mod migration_admin_token;
+mod migration_admin_token_zones;
+mod migration_ip_policy;
+mod migration_last_known_ip;
+mod migration_legacy_signatures;
 mod migration_many_admin;
 mod migration_signing_account;
+mod migration_signing_key;
+mod migration_ttl;
+mod migration_update_history;
 
 pub struct Migrator;
 
@@ -13,6 +25,18 @@ impl MigratorTrait for Migrator {
             Box::new(initialize_table::Migration),
             Box::new(migration_many_admin::Migration),
             Box::new(migration_signing_account::Migration),
+            Box::new(migration_last_known_ip::Migration),
+            Box::new(migration_update_history::Migration),
+            Box::new(migration_signing_key::Migration),
+            Box::new(migration_admin_disabled::Migration),
+            Box::new(migration_admin_role::Migration),
+            Box::new(migration_admin_token::Migration),
+            Box::new(migration_account_hostname::Migration),
+            Box::new(migration_ttl::Migration),
+            Box::new(migration_acme_challenge::Migration),
+            Box::new(migration_legacy_signatures::Migration),
+            Box::new(migration_ip_policy::Migration),
+            Box::new(migration_admin_token_zones::Migration),
         ]
     }
 }
@@ -0,0 +1,65 @@
+use sea_orm_migration::prelude::*;
+
+/// Tenth migration
+///
+/// Creates the account_hostname table, holding the extra hostnames updated by an account
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountHostname::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountHostname::Hostname)
+                            .string_len(255)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountHostname::Domain)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountHostname::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_account_hostname_domain")
+                    .table(AccountHostname::Table)
+                    .col(AccountHostname::Domain)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountHostname::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AccountHostname {
+    Table,
+    Hostname,
+    Domain,
+    CreatedAt,
+}
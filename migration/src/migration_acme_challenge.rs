@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+/// Twelfth migration
+///
+/// Creates the acme_challenge table, holding the TXT values set by the accounts for ACME DNS-01 challenges
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AcmeChallenge::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AcmeChallenge::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AcmeChallenge::Name)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AcmeChallenge::Value)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AcmeChallenge::Domain)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AcmeChallenge::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_acme_challenge_name")
+                    .table(AcmeChallenge::Table)
+                    .col(AcmeChallenge::Name)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_acme_challenge_domain")
+                    .table(AcmeChallenge::Table)
+                    .col(AcmeChallenge::Domain)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AcmeChallenge::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AcmeChallenge {
+    Table,
+    Id,
+    Name,
+    Value,
+    Domain,
+    CreatedAt,
+}
@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+/// Seventh migration
+///
+/// Adds the disabled column to the admin_account table
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .add_column(
+                        ColumnDef::new(AdminAccount::Disabled)
+                            .boolean()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .drop_column(AdminAccount::Disabled)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdminAccount {
+    Table,
+    Disabled,
+}
@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+/// Eighth migration
+///
+/// Adds the role and zones columns to the admin_account table
+///
+/// The bootstrapped `admin` account becomes a superadmin, the other admins become
+/// zone operators without any zone, and only keep access to the accounts they created.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .add_column(
+                        ColumnDef::new(AdminAccount::Role)
+                            .string_len(16)
+                            .not_null()
+                            .default("operator"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .add_column(ColumnDef::new(AdminAccount::Zones).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(AdminAccount::Table)
+                    .value(AdminAccount::Role, "superadmin")
+                    .and_where(Expr::col(AdminAccount::User).eq("admin"))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .drop_column(AdminAccount::Zones)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminAccount::Table)
+                    .drop_column(AdminAccount::Role)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdminAccount {
+    Table,
+    User,
+    Role,
+    Zones,
+}
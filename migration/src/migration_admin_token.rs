@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+/// Ninth migration
+///
+/// Creates the admin_token table, holding the API tokens of the admins
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AdminToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AdminToken::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AdminToken::User).string().not_null())
+                    .col(ColumnDef::new(AdminToken::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(AdminToken::TokenHash)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AdminToken::ReadOnly)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(AdminToken::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AdminToken::ExpiresAt).timestamp_with_time_zone())
+                    .col(ColumnDef::new(AdminToken::LastUsedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_admin_token_user")
+                    .table(AdminToken::Table)
+                    .col(AdminToken::User)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AdminToken::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdminToken {
+    Table,
+    Id,
+    User,
+    Name,
+    TokenHash,
+    ReadOnly,
+    CreatedAt,
+    ExpiresAt,
+    LastUsedAt,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+/// Fifteenth migration
+///
+/// Adds the zones column to the admin_token table
+///
+/// The tokens minted before aren't limited to any zone.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminToken::Table)
+                    .add_column(ColumnDef::new(AdminToken::Zones).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AdminToken::Table)
+                    .drop_column(AdminToken::Zones)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdminToken {
+    Table,
+    Zones,
+}
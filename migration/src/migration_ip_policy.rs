@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+/// Fourteenth migration
+///
+/// Creates the ip_policy table, holding the addresses an account can set other than the IP of its client
+///
+/// Accounts without a policy can set any address.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IpPolicy::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IpPolicy::Domain)
+                            .string_len(255)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(IpPolicy::Mode).string_len(16).not_null())
+                    .col(ColumnDef::new(IpPolicy::Networks).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IpPolicy::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IpPolicy {
+    Table,
+    Domain,
+    Mode,
+    Networks,
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+/// Fourth migration
+///
+/// Creates the last_known_ip table
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LastKnownIp::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LastKnownIp::Domain)
+                            .string_len(255)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LastKnownIp::Ipv4).string_len(15))
+                    .col(ColumnDef::new(LastKnownIp::Ipv6).string_len(39))
+                    .col(
+                        ColumnDef::new(LastKnownIp::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LastKnownIp::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum LastKnownIp {
+    Table,
+    Domain,
+    Ipv4,
+    Ipv6,
+    UpdatedAt,
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+/// Thirteenth migration
+///
+/// Adds the legacy_signatures column to the signing_account table
+///
+/// Accounts created before keep accepting the signatures that don't include the IP.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .add_column(
+                        ColumnDef::new(SigningAccount::LegacySignatures)
+                            .boolean()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .drop_column(SigningAccount::LegacySignatures)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SigningAccount {
+    Table,
+    LegacySignatures,
+}
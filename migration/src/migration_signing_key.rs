@@ -0,0 +1,169 @@
+use openssl::{pkey::PKey, sha::sha256};
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+/// Sixth migration
+///
+/// Moves the public keys of the signing accounts to the signing_key table,
+/// allowing an account to have many keys
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SigningKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SigningKey::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SigningKey::Domain).string().not_null())
+                    .col(
+                        ColumnDef::new(SigningKey::Fingerprint)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SigningKey::Label).string())
+                    .col(ColumnDef::new(SigningKey::PublicKey).text().not_null())
+                    .col(
+                        ColumnDef::new(SigningKey::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(SigningKey::NotAfter).timestamp_with_time_zone())
+                    .col(ColumnDef::new(SigningKey::RevokedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_signing_key_domain")
+                    .table(SigningKey::Table)
+                    .col(SigningKey::Domain)
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        let select = Query::select()
+            .columns([SigningAccount::Domain, SigningAccount::PublicKey])
+            .from(SigningAccount::Table)
+            .to_owned();
+        let accounts = db
+            .query_all(db.get_database_backend().build(&select))
+            .await?;
+
+        for account in accounts {
+            let domain: String = account.try_get("", "domain")?;
+            let public_key: String = account.try_get("", "public_key")?;
+
+            let fingerprint = fingerprint(&public_key).ok_or_else(|| {
+                DbErr::Migration(format!("Invalid public key for the domain {domain}"))
+            })?;
+
+            manager
+                .exec_stmt(
+                    Query::insert()
+                        .into_table(SigningKey::Table)
+                        .columns([
+                            SigningKey::Domain,
+                            SigningKey::Fingerprint,
+                            SigningKey::PublicKey,
+                            SigningKey::CreatedAt,
+                        ])
+                        .values_panic([
+                            domain.into(),
+                            fingerprint.into(),
+                            public_key.into(),
+                            Expr::current_timestamp().into(),
+                        ])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .drop_column(SigningAccount::PublicKey)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .add_column(
+                        ColumnDef::new(SigningAccount::PublicKey)
+                            .text()
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Keeps the oldest key of each account
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            db.get_database_backend(),
+            "UPDATE signing_account SET public_key = COALESCE((SELECT signing_key.public_key \
+             FROM signing_key WHERE signing_key.domain = signing_account.domain \
+             ORDER BY signing_key.id LIMIT 1), '')",
+        ))
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(SigningKey::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// The hexadecimal SHA-256 digest of the DER encoded public key
+fn fingerprint(pem: &str) -> Option<String> {
+    let der = PKey::public_key_from_pem(pem.as_bytes())
+        .ok()?
+        .public_key_to_der()
+        .ok()?;
+
+    Some(sha256(&der).iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[derive(DeriveIden)]
+enum SigningAccount {
+    Table,
+    Domain,
+    PublicKey,
+}
+
+#[derive(DeriveIden)]
+enum SigningKey {
+    Table,
+    Id,
+    Domain,
+    Fingerprint,
+    Label,
+    PublicKey,
+    CreatedAt,
+    NotAfter,
+    RevokedAt,
+}
@@ -0,0 +1,96 @@
+use sea_orm_migration::prelude::*;
+
+/// Eleventh migration
+///
+/// Adds the ttl column to the password_account and signing_account tables,
+/// and creates the zone_ttl table, holding the default TTL of the zones
+///
+/// Accounts without a TTL use the default of their zone.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordAccount::Table)
+                    .add_column(ColumnDef::new(PasswordAccount::Ttl).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .add_column(ColumnDef::new(SigningAccount::Ttl).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ZoneTtl::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ZoneTtl::Zone)
+                            .string_len(255)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ZoneTtl::Ttl).integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ZoneTtl::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SigningAccount::Table)
+                    .drop_column(SigningAccount::Ttl)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordAccount::Table)
+                    .drop_column(PasswordAccount::Ttl)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordAccount {
+    Table,
+    Ttl,
+}
+
+#[derive(DeriveIden)]
+enum SigningAccount {
+    Table,
+    Ttl,
+}
+
+#[derive(DeriveIden)]
+enum ZoneTtl {
+    Table,
+    Zone,
+    Ttl,
+}
@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::*;
+
+/// Fifth migration
+///
+/// Creates the update_history table
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UpdateHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UpdateHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UpdateHistory::Domain)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UpdateHistory::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UpdateHistory::Status)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UpdateHistory::OldIp).string())
+                    .col(ColumnDef::new(UpdateHistory::NewIp).string())
+                    .col(
+                        ColumnDef::new(UpdateHistory::AuthMethod)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UpdateHistory::SourceAddress)
+                            .string_len(45)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UpdateHistory::UserAgent).string())
+                    .col(ColumnDef::new(UpdateHistory::ChangeId).string())
+                    .col(ColumnDef::new(UpdateHistory::Reason).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_update_history_domain_created_at")
+                    .table(UpdateHistory::Table)
+                    .col(UpdateHistory::Domain)
+                    .col(UpdateHistory::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UpdateHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UpdateHistory {
+    Table,
+    Id,
+    Domain,
+    CreatedAt,
+    Status,
+    OldIp,
+    NewIp,
+    AuthMethod,
+    SourceAddress,
+    UserAgent,
+    ChangeId,
+    Reason,
+}
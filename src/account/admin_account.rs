@@ -1,13 +1,36 @@
-use crate::{utils::compare_with_hash, utils::Credentials};
-use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
-use rand::rngs::OsRng;
+use std::str::FromStr;
+
+use super::{admin_token, HostnamePattern};
+use crate::{utils::compare_with_hash, utils::hash_password, utils::Credentials};
+use chrono::Utc;
+use fqdn::FQDN;
 use rocket::{
     async_trait, http,
     request::{self, FromRequest},
+    serde::{Deserialize, Serialize},
     Request,
 };
 use rocket_basicauth::BasicAuth;
-use sea_orm::{entity::prelude::*, Set};
+use sea_orm::{entity::prelude::*, Condition, QueryOrder, Set};
+
+/// What an admin is allowed to do
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum AdminRole {
+    /// Manages every account and the other admins
+    #[sea_orm(string_value = "superadmin")]
+    SuperAdmin,
+    /// Creates and manages the accounts inside its zones, and the accounts it created
+    #[default]
+    #[sea_orm(string_value = "operator")]
+    Operator,
+    /// Reads the accounts, keys, records and history inside its zones
+    #[sea_orm(string_value = "auditor")]
+    Auditor,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "admin_account")]
@@ -15,6 +38,14 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub user: String,
     pub password_hash: String,
+    /// If the admin is disabled
+    ///
+    /// Must be true to disable the admin
+    /// Null or false means the admin is enabled
+    pub disabled: Option<bool>,
+    pub role: AdminRole,
+    /// The FQDN suffixes the operators and auditors are limited to, separated by `;`
+    pub zones: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -22,28 +53,295 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
-pub struct AdminAccount(String);
+/// The admin bootstrapped at startup, which can't be deleted, disabled or demoted
+pub const SUPER_ADMIN: &str = "admin";
+
+pub struct AdminAccount {
+    user: String,
+    role: AdminRole,
+    zones: Vec<FQDN>,
+    /// Authenticated with a read-only API token
+    read_only: bool,
+    /// The zones of the API token the admin authenticated with, when it is limited to some
+    token_zones: Option<Vec<FQDN>>,
+}
+
+/// An admin as listed to the superadmins
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AdminSummary {
+    pub user: String,
+    pub disabled: bool,
+    pub role: AdminRole,
+    pub zones: Vec<String>,
+}
+
+/// Parses the zones column, ignoring the suffixes which aren't valid domain names
+fn parse_zones(zones: Option<&str>) -> Vec<FQDN> {
+    zones
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|z| !z.is_empty())
+        .filter_map(|z| FQDN::from_str(z).ok())
+        .collect()
+}
+
+/// Whether the domain, or the suffix of the subtree, is inside one of the zones
+fn in_any_zone(zones: &[FQDN], domain: &str) -> bool {
+    HostnamePattern::from_str(domain)
+        .is_ok_and(|domain| zones.iter().any(|zone| domain.fqdn().is_subdomain_of(zone)))
+}
+
+/// Formats the zones for the zones column, `None` when there are none
+pub(super) fn join_zones(zones: &[FQDN]) -> Option<String> {
+    if zones.is_empty() {
+        return None;
+    }
+
+    Some(
+        zones
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(";"),
+    )
+}
 
 impl AdminAccount {
-    #[allow(unused)]
     pub fn get_user(&self) -> &str {
-        &self.0
+        &self.user
+    }
+
+    pub fn is_super_admin(&self) -> bool {
+        self.role == AdminRole::SuperAdmin && !self.read_only && self.token_zones.is_none()
+    }
+
+    /// Whether the admin authenticated with a read-only or zone-limited API token
+    ///
+    /// Such tokens can't change the admin itself, its password or its tokens.
+    pub fn is_limited_token(&self) -> bool {
+        self.read_only || self.token_zones.is_some()
+    }
+
+    /// Whether the admin can change anything, auditors and read-only tokens can't
+    pub fn can_write(&self) -> bool {
+        self.role != AdminRole::Auditor && !self.read_only
+    }
+
+    /// Whether the domain, or the suffix of the subtree, is inside one of the zones of the admin
+    pub fn in_zones(&self, domain: &str) -> bool {
+        in_any_zone(&self.zones, domain)
+    }
+
+    /// Whether the domain is inside the zones of the API token, always true without token zones
+    fn in_token_zones(&self, domain: &str) -> bool {
+        self.token_zones
+            .as_ref()
+            .is_none_or(|zones| in_any_zone(zones, domain))
+    }
+
+    /// Whether the zone shares domains with the zones of the admin
+    pub fn overlaps_zone(&self, zone: &FQDN) -> bool {
+        let overlaps = |zones: &[FQDN]| {
+            zones
+                .iter()
+                .any(|z| z.is_subdomain_of(zone) || zone.is_subdomain_of(z))
+        };
+
+        (self.role == AdminRole::SuperAdmin || overlaps(&self.zones))
+            && self.token_zones.as_deref().is_none_or(overlaps)
+    }
+
+    /// Whether the admin can create an account for the domain
+    pub fn can_create(&self, domain: &str) -> bool {
+        self.can_write()
+            && (self.role == AdminRole::SuperAdmin || self.in_zones(domain))
+            && self.in_token_zones(domain)
+    }
+
+    /// Whether the admin can change the account of the domain, created by `created_by`
+    pub fn can_manage(&self, domain: &str, created_by: Option<&str>) -> bool {
+        self.can_write() && self.can_read(domain, created_by)
+    }
+
+    /// Whether the admin can read the account, keys, records and history of the domain
+    pub fn can_read(&self, domain: &str, created_by: Option<&str>) -> bool {
+        let allowed = match self.role {
+            AdminRole::SuperAdmin => true,
+            AdminRole::Operator => self.in_zones(domain) || created_by == Some(&self.user),
+            AdminRole::Auditor => self.in_zones(domain),
+        };
+
+        allowed && self.in_token_zones(domain)
+    }
+
+    /// The database condition on the accounts the admin can read, matching `can_read`
+    pub fn read_condition<C: ColumnTrait>(&self, domain: C, created_by: C) -> Condition {
+        let in_zones = |zones: &[FQDN]| {
+            zones.iter().fold(Condition::any(), |condition, zone| {
+                condition.add(super::zone_condition(domain, zone))
+            })
+        };
+
+        let allowed = match self.role {
+            AdminRole::SuperAdmin => Condition::all(),
+            AdminRole::Operator => in_zones(&self.zones).add(created_by.eq(&self.user)),
+            AdminRole::Auditor => in_zones(&self.zones),
+        };
+
+        match &self.token_zones {
+            Some(token_zones) => Condition::all().add(allowed).add(in_zones(token_zones)),
+            None => allowed,
+        }
+    }
+
+    pub async fn list(db: &DbConn) -> Result<Vec<AdminSummary>, DbErr> {
+        Ok(Entity::find()
+            .order_by_asc(Column::User)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|m| AdminSummary {
+                zones: parse_zones(m.zones.as_deref())
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                user: m.user,
+                disabled: m.disabled.unwrap_or(false),
+                role: m.role,
+            })
+            .collect())
+    }
+
+    /// Deletes the admin and its API tokens, returning false when it doesn't exist
+    pub async fn delete(user: &str, db: &DbConn) -> Result<bool, DbErr> {
+        admin_token::revoke_all(user, db).await?;
+        Entity::delete_by_id(user)
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    /// Replaces the password of the admin, returning false when it doesn't exist
+    pub async fn set_password(user: &str, password: &str, db: &DbConn) -> Result<bool, DbErr> {
+        Self::set_password_hash(user, &hash_password(password), db).await
+    }
+
+    pub async fn set_password_hash(
+        user: &str,
+        password_hash: &str,
+        db: &DbConn,
+    ) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::PasswordHash, Expr::value(password_hash))
+            .filter(Column::User.eq(user))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    pub async fn set_disabled(user: &str, disabled: bool, db: &DbConn) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Disabled, Expr::value(disabled))
+            .filter(Column::User.eq(user))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    /// Replaces the role and the zones of the admin, returning false when it doesn't exist
+    pub async fn set_role(
+        user: &str,
+        role: AdminRole,
+        zones: &[FQDN],
+        db: &DbConn,
+    ) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Role, Expr::value(role))
+            .col_expr(Column::Zones, Expr::value(join_zones(zones)))
+            .filter(Column::User.eq(user))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+}
+
+impl ActiveModel {
+    pub fn new(credentials: Credentials, role: AdminRole, zones: &[FQDN]) -> Self {
+        Self {
+            role: Set(role),
+            zones: Set(join_zones(zones)),
+            ..credentials.into()
+        }
     }
 }
 
 impl From<crate::utils::Credentials> for ActiveModel {
     fn from(value: Credentials) -> Self {
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash: String = argon2
-            .hash_password(value.password.as_bytes(), &salt)
-            .unwrap()
-            .to_string();
-
         Self {
             user: Set(value.username),
-            password_hash: Set(password_hash),
+            password_hash: Set(hash_password(&value.password)),
+            disabled: Set(Some(false)),
+            role: Set(AdminRole::default()),
+            zones: Set(None),
+        }
+    }
+}
+
+impl From<Model> for AdminAccount {
+    fn from(model: Model) -> Self {
+        Self {
+            zones: parse_zones(model.zones.as_deref()),
+            user: model.user,
+            role: model.role,
+            read_only: false,
+            token_zones: None,
+        }
+    }
+}
+
+/// Authenticates an admin with an API token
+async fn from_token(token: &str, db: &DbConn) -> request::Outcome<AdminAccount, ()> {
+    let token = match admin_token::find_and_touch(token, db).await {
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            return request::Outcome::Error((http::Status::InternalServerError, ()));
+        }
+        Ok(token) => token,
+    };
+
+    let token = match token {
+        Some(token) if !token.is_expired(Utc::now()) => token,
+        Some(token) => {
+            log::info!(
+                "The expired API token {} of the admin \"{}\" was used",
+                token.id,
+                token.user
+            );
+            return request::Outcome::Error((http::Status::Unauthorized, ()));
+        }
+        None => return request::Outcome::Error((http::Status::Unauthorized, ())),
+    };
+
+    match Entity::find_by_id(&token.user).one(db).await {
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            request::Outcome::Error((http::Status::InternalServerError, ()))
+        }
+        Ok(None) => request::Outcome::Error((http::Status::Unauthorized, ())),
+        Ok(Some(d)) if d.disabled == Some(true) => {
+            log::warn!("The disabled admin \"{}\" attempted to log in", d.user);
+            request::Outcome::Error((http::Status::Forbidden, ()))
         }
+        Ok(Some(d)) => request::Outcome::Success(AdminAccount {
+            read_only: token.read_only,
+            token_zones: token
+                .zones
+                .is_some()
+                .then(|| parse_zones(token.zones.as_deref())),
+            ..d.into()
+        }),
     }
 }
 
@@ -53,6 +351,15 @@ impl<'r> FromRequest<'r> for AdminAccount {
 
     async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
         let db: &DbConn = request.rocket().state::<DbConn>().unwrap();
+
+        if let Some(token) = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+        {
+            return from_token(token.trim(), db).await;
+        }
+
         let auth: BasicAuth;
         match request.guard::<BasicAuth>().await {
             request::Outcome::Error(_) => {
@@ -74,7 +381,11 @@ impl<'r> FromRequest<'r> for AdminAccount {
             None => request::Outcome::Error((http::Status::Unauthorized, ())),
             Some(d) => match compare_with_hash(&auth.password, &d.password_hash) {
                 Err(_) => request::Outcome::Error((http::Status::Unauthorized, ())),
-                Ok(()) => request::Outcome::Success(AdminAccount(d.user)),
+                Ok(()) if d.disabled == Some(true) => {
+                    log::warn!("The disabled admin \"{}\" attempted to log in", d.user);
+                    request::Outcome::Error((http::Status::Forbidden, ()))
+                }
+                Ok(()) => request::Outcome::Success(d.into()),
             },
         }
     }
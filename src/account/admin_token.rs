@@ -0,0 +1,150 @@
+/// The API tokens of the admins
+///
+/// Tokens are sent as `Authorization: Bearer <token>` in place of the password of the admin.
+/// Only their SHA-256 digest is stored, the token itself is shown once when it is minted.
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use fqdn::FQDN;
+use openssl::sha::sha256;
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    rngs::StdRng,
+};
+use rocket::serde::Serialize;
+use sea_orm::{entity::prelude::*, QueryOrder, Set};
+
+use super::admin_account::join_zones;
+
+/// Prefix of the tokens, so they can be recognized in logs and secret scanners
+const TOKEN_PREFIX: &str = "ftddns_";
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "admin_token")]
+#[serde(crate = "rocket::serde")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// The admin the token authenticates as
+    pub user: String,
+    /// A free text to tell the tokens apart
+    pub name: String,
+    /// The hexadecimal SHA-256 digest of the token
+    #[serde(skip)]
+    pub token_hash: String,
+    /// The token can only be used for the routes which don't change anything
+    pub read_only: bool,
+    pub created_at: DateTimeUtc,
+    /// The token is refused after this time
+    pub expires_at: Option<DateTimeUtc>,
+    pub last_used_at: Option<DateTimeUtc>,
+    /// The FQDN suffixes the token is limited to, separated by `;`, on top of the zones of the admin
+    pub zones: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// A newly minted token, the only time the token itself is returned
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MintedToken {
+    #[serde(flatten)]
+    pub model: Model,
+    pub token: String,
+}
+
+fn hash_token(token: &str) -> String {
+    sha256(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+pub async fn mint(
+    user: &str,
+    name: String,
+    read_only: bool,
+    expires_at: Option<DateTime<Utc>>,
+    zones: &[FQDN],
+    rng: &Mutex<StdRng>,
+    db: &DbConn,
+) -> Result<MintedToken, DbErr> {
+    let token = format!(
+        "{TOKEN_PREFIX}{}",
+        Alphanumeric.sample_string(&mut *rng.lock().unwrap(), 40)
+    );
+
+    let model = ActiveModel {
+        user: Set(user.to_owned()),
+        name: Set(name),
+        token_hash: Set(hash_token(&token)),
+        read_only: Set(read_only),
+        created_at: Set(Utc::now()),
+        expires_at: Set(expires_at),
+        last_used_at: Set(None),
+        zones: Set(join_zones(zones)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(MintedToken { model, token })
+}
+
+/// Finds the token, expired ones included, and records its use
+pub async fn find_and_touch(token: &str, db: &DbConn) -> Result<Option<Model>, DbErr> {
+    let Some(model) = Entity::find()
+        .filter(Column::TokenHash.eq(hash_token(token)))
+        .one(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    Entity::update_many()
+        .col_expr(Column::LastUsedAt, Expr::value(Utc::now()))
+        .filter(Column::Id.eq(model.id))
+        .exec(db)
+        .await?;
+
+    Ok(Some(model))
+}
+
+/// The tokens of the admin, oldest first
+pub async fn find_by_user(user: &str, db: &DbConn) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::User.eq(user))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await
+}
+
+/// Revokes a token, returning false when the admin has no token with this ID
+///
+/// Without a user, the token is revoked whichever admin it belongs to.
+pub async fn revoke(id: i64, user: Option<&str>, db: &DbConn) -> Result<bool, DbErr> {
+    let mut delete = Entity::delete_many().filter(Column::Id.eq(id));
+    if let Some(user) = user {
+        delete = delete.filter(Column::User.eq(user));
+    }
+
+    delete.exec(db).await.map(|r| r.rows_affected > 0)
+}
+
+pub async fn revoke_all(user: &str, db: &DbConn) -> Result<(), DbErr> {
+    Entity::delete_many()
+        .filter(Column::User.eq(user))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+/// The extra hostnames updated by an account
+///
+/// The domain of the account always is one of its hostnames, and isn't stored here.
+/// A hostname belongs to at most one account.
+use std::{fmt, str::FromStr};
+
+use chrono::Utc;
+use fqdn::FQDN;
+use rocket::serde::Serialize;
+use sea_orm::{entity::prelude::*, QueryOrder, Set};
+
+/// Prefix of the subtree patterns
+const SUBTREE_PREFIX: &str = "*.";
+
+/// A name an account can update, as stored for the domain and the hostnames of the accounts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostnamePattern {
+    /// A single hostname
+    Exact(FQDN),
+    /// Every name strictly under the suffix, written `*.suffix`
+    ///
+    /// The records are created when a name is first updated.
+    Subtree(FQDN),
+}
+
+impl HostnamePattern {
+    /// The hostname, or the suffix of the subtree
+    pub fn fqdn(&self) -> &FQDN {
+        match self {
+            Self::Exact(fqdn) | Self::Subtree(fqdn) => fqdn,
+        }
+    }
+
+    pub fn exact(&self) -> Option<&FQDN> {
+        match self {
+            Self::Exact(fqdn) => Some(fqdn),
+            Self::Subtree(_) => None,
+        }
+    }
+
+    /// Whether the account can update the hostname through this pattern
+    pub fn matches(&self, hostname: &FQDN) -> bool {
+        match self {
+            Self::Exact(fqdn) => fqdn == hostname,
+            Self::Subtree(suffix) => hostname != suffix && hostname.is_subdomain_of(suffix),
+        }
+    }
+
+    /// Whether a hostname could be updated through both patterns
+    pub fn overlaps(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Exact(fqdn), pattern) | (pattern, Self::Exact(fqdn)) => pattern.matches(fqdn),
+            (Self::Subtree(a), Self::Subtree(b)) => a.is_subdomain_of(b) || b.is_subdomain_of(a),
+        }
+    }
+}
+
+impl FromStr for HostnamePattern {
+    type Err = fqdn::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(SUBTREE_PREFIX) {
+            Some(suffix) => FQDN::from_str(suffix).map(Self::Subtree),
+            None => FQDN::from_str(s).map(Self::Exact),
+        }
+    }
+}
+
+impl fmt::Display for HostnamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(fqdn) => write!(f, "{fqdn}"),
+            Self::Subtree(suffix) => write!(f, "{SUBTREE_PREFIX}{suffix}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "account_hostname")]
+#[serde(crate = "rocket::serde")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub hostname: String,
+    /// The domain of the account owning the hostname
+    pub domain: String,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+pub async fn add(domain: &str, hostname: &str, db: &DbConn) -> Result<Model, DbErr> {
+    ActiveModel {
+        hostname: Set(hostname.to_owned()),
+        domain: Set(domain.to_owned()),
+        created_at: Set(Utc::now()),
+    }
+    .insert(db)
+    .await
+}
+
+/// The domain of the account owning the hostname
+pub async fn owner(hostname: &str, db: &DbConn) -> Result<Option<String>, DbErr> {
+    Ok(Entity::find_by_id(hostname)
+        .one(db)
+        .await?
+        .map(|model| model.domain))
+}
+
+/// The extra hostnames of the account, sorted
+pub async fn find_by_domain(domain: &str, db: &DbConn) -> Result<Vec<String>, DbErr> {
+    Ok(Entity::find()
+        .filter(Column::Domain.eq(domain))
+        .order_by_asc(Column::Hostname)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|model| model.hostname)
+        .collect())
+}
+
+/// Removes a hostname of the account, returning false when the account doesn't have it
+pub async fn remove(domain: &str, hostname: &str, db: &DbConn) -> Result<bool, DbErr> {
+    Entity::delete_many()
+        .filter(Column::Domain.eq(domain))
+        .filter(Column::Hostname.eq(hostname))
+        .exec(db)
+        .await
+        .map(|r| r.rows_affected > 0)
+}
+
+pub async fn delete_by_domain(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+    Entity::delete_many()
+        .filter(Column::Domain.eq(domain))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// The hostnames matching the SQL `LIKE` pattern, along with the domain of their account
+pub async fn find_like(pattern: &str, db: &DbConn) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::Hostname.like(pattern))
+        .all(db)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> HostnamePattern {
+        HostnamePattern::from_str(s).unwrap()
+    }
+
+    fn fqdn(s: &str) -> FQDN {
+        FQDN::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_patterns() {
+        assert_eq!(
+            pattern("*.dyn.example.com"),
+            HostnamePattern::Subtree(fqdn("dyn.example.com"))
+        );
+        assert_eq!(
+            pattern("a.dyn.example.com"),
+            HostnamePattern::Exact(fqdn("a.dyn.example.com"))
+        );
+        assert_eq!(
+            pattern("*.dyn.example.com").to_string(),
+            "*.dyn.example.com"
+        );
+    }
+
+    #[test]
+    fn exact_matches_only_itself() {
+        let exact = pattern("a.dyn.example.com");
+
+        assert!(exact.matches(&fqdn("a.dyn.example.com")));
+        assert!(!exact.matches(&fqdn("b.a.dyn.example.com")));
+        assert!(!exact.matches(&fqdn("dyn.example.com")));
+    }
+
+    #[test]
+    fn subtree_matches_strictly_under_the_suffix() {
+        let subtree = pattern("*.a.dyn.example.com");
+
+        assert!(subtree.matches(&fqdn("b.a.dyn.example.com")));
+        assert!(subtree.matches(&fqdn("c.b.a.dyn.example.com")));
+        assert!(!subtree.matches(&fqdn("a.dyn.example.com")));
+        assert!(!subtree.matches(&fqdn("xa.dyn.example.com")));
+        assert!(!subtree.matches(&fqdn("dyn.example.com")));
+    }
+
+    #[test]
+    fn exact_overlaps() {
+        let exact = pattern("b.a.dyn.example.com");
+
+        assert!(exact.overlaps(&pattern("b.a.dyn.example.com")));
+        assert!(!exact.overlaps(&pattern("c.a.dyn.example.com")));
+        assert!(exact.overlaps(&pattern("*.a.dyn.example.com")));
+        assert!(pattern("*.a.dyn.example.com").overlaps(&exact));
+        assert!(exact.overlaps(&pattern("*.dyn.example.com")));
+        // The suffix of a subtree isn't part of it
+        assert!(!pattern("a.dyn.example.com").overlaps(&pattern("*.a.dyn.example.com")));
+        assert!(!pattern("*.a.dyn.example.com").overlaps(&pattern("a.dyn.example.com")));
+    }
+
+    #[test]
+    fn subtree_overlaps() {
+        let subtree = pattern("*.a.dyn.example.com");
+
+        assert!(subtree.overlaps(&pattern("*.a.dyn.example.com")));
+        assert!(subtree.overlaps(&pattern("*.b.a.dyn.example.com")));
+        assert!(pattern("*.b.a.dyn.example.com").overlaps(&subtree));
+        assert!(subtree.overlaps(&pattern("*.dyn.example.com")));
+        assert!(!subtree.overlaps(&pattern("*.b.dyn.example.com")));
+        assert!(!subtree.overlaps(&pattern("*.xa.dyn.example.com")));
+    }
+}
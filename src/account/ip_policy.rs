@@ -0,0 +1,234 @@
+/// The addresses an account can set other than the IP of its client
+///
+/// Accounts without a policy can set any address.
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use rocket::serde::{Deserialize, Serialize};
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
+
+use crate::ip::{Addresses, IP};
+
+/// Which addresses requested explicitly an account can set
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum IpOverride {
+    /// Only the IP of the client
+    #[sea_orm(string_value = "observed")]
+    Observed,
+    /// Any address
+    #[default]
+    #[sea_orm(string_value = "any")]
+    Any,
+    /// The IP of the client, or the addresses inside the networks of the policy
+    #[sea_orm(string_value = "networks")]
+    Networks,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "ip_policy")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    /// The domain of the account
+    pub domain: String,
+    pub mode: IpOverride,
+    /// The networks in CIDR notation, separated by `;`
+    pub networks: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// The IP policy of an account, as shown to the admins
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IpPolicy {
+    pub mode: IpOverride,
+    #[serde(default)]
+    pub networks: Vec<IpNet>,
+}
+
+impl IpPolicy {
+    /// Whether the account can set every address requested, from the client IP
+    pub fn allows(&self, addresses: &Addresses, client: &IP) -> bool {
+        let client = client.get();
+        let requested = addresses
+            .ipv4
+            .map(IpAddr::V4)
+            .into_iter()
+            .chain(addresses.ipv6.map(IpAddr::V6));
+
+        requested.into_iter().all(|ip| {
+            ip == client
+                || match self.mode {
+                    IpOverride::Observed => false,
+                    IpOverride::Any => true,
+                    IpOverride::Networks => self.networks.iter().any(|net| net.contains(&ip)),
+                }
+        })
+    }
+}
+
+impl From<Model> for IpPolicy {
+    fn from(value: Model) -> Self {
+        Self {
+            mode: value.mode,
+            networks: value
+                .networks
+                .as_deref()
+                .unwrap_or_default()
+                .split(';')
+                .filter_map(|net| net.parse().ok())
+                .collect(),
+        }
+    }
+}
+
+/// The IP policy of the account of the domain, the default one when it has none
+pub async fn find(domain: &str, db: &DbConn) -> Result<IpPolicy, DbErr> {
+    Ok(Entity::find_by_id(domain)
+        .one(db)
+        .await?
+        .map(IpPolicy::from)
+        .unwrap_or_default())
+}
+
+/// Replaces the IP policy of the account of the domain
+pub async fn set(domain: &str, policy: &IpPolicy, db: &DbConn) -> Result<(), DbErr> {
+    let networks: Vec<String> = policy.networks.iter().map(IpNet::to_string).collect();
+
+    Entity::insert(ActiveModel {
+        domain: Set(domain.to_owned()),
+        mode: Set(policy.mode),
+        networks: Set((!networks.is_empty()).then(|| networks.join(";"))),
+    })
+    .on_conflict(
+        OnConflict::column(Column::Domain)
+            .update_columns([Column::Mode, Column::Networks])
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_by_domain(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+    Entity::delete_by_id(domain).exec(db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rocket::serde::json;
+
+    use super::*;
+
+    fn policy(mode: IpOverride, networks: &[&str]) -> IpPolicy {
+        IpPolicy {
+            mode,
+            networks: networks.iter().map(|net| net.parse().unwrap()).collect(),
+        }
+    }
+
+    fn allows(policy: &IpPolicy, list: &str, client: &str) -> bool {
+        policy.allows(
+            &Addresses::from_list(list).unwrap(),
+            &IP::from_str(client).unwrap(),
+        )
+    }
+
+    #[test]
+    fn observed_only_allows_the_client_ip() {
+        let observed = policy(IpOverride::Observed, &[]);
+
+        assert!(allows(&observed, "192.0.2.1", "192.0.2.1"));
+        assert!(allows(&observed, "2001:db8::1", "2001:db8::1"));
+        assert!(allows(&observed, "192.0.2.1", "::ffff:192.0.2.1"));
+        assert!(!allows(&observed, "192.0.2.2", "192.0.2.1"));
+        assert!(!allows(&observed, "192.0.2.1,2001:db8::1", "192.0.2.1"));
+    }
+
+    #[test]
+    fn any_allows_every_address() {
+        let any = IpPolicy::default();
+
+        assert_eq!(any.mode, IpOverride::Any);
+        assert!(allows(&any, "203.0.113.7", "192.0.2.1"));
+        assert!(allows(&any, "203.0.113.7,2001:db8::7", "2001:db8::1"));
+    }
+
+    #[test]
+    fn networks_allow_the_addresses_inside_them() {
+        let networks = policy(
+            IpOverride::Networks,
+            &["198.51.100.0/24", "2001:db8:100::/48"],
+        );
+
+        assert!(allows(&networks, "198.51.100.9", "192.0.2.1"));
+        assert!(!allows(&networks, "198.51.101.9", "192.0.2.1"));
+        assert!(allows(&networks, "2001:db8:100:5::1", "192.0.2.1"));
+        assert!(!allows(&networks, "2001:db8:101::1", "192.0.2.1"));
+        assert!(allows(
+            &networks,
+            "198.51.100.9,2001:db8:100::1",
+            "192.0.2.1"
+        ));
+        assert!(!allows(
+            &networks,
+            "198.51.100.9,2001:db8:101::1",
+            "192.0.2.1"
+        ));
+        // The client IP is allowed outside of the networks
+        assert!(allows(&networks, "192.0.2.1,2001:db8:100::1", "192.0.2.1"));
+    }
+
+    #[test]
+    fn parses_the_json_policy() {
+        assert_eq!(
+            json::from_str::<IpPolicy>(
+                r#"{"mode": "networks", "networks": ["198.51.100.0/24", "2001:db8:100::/48"]}"#
+            )
+            .unwrap(),
+            policy(
+                IpOverride::Networks,
+                &["198.51.100.0/24", "2001:db8:100::/48"]
+            )
+        );
+        assert_eq!(
+            json::from_str::<IpPolicy>(r#"{"mode": "observed"}"#).unwrap(),
+            policy(IpOverride::Observed, &[])
+        );
+        assert_eq!(
+            json::from_str::<IpPolicy>(r#"{"mode": "any", "networks": []}"#).unwrap(),
+            IpPolicy::default()
+        );
+
+        for invalid in [
+            r#"{"networks": ["198.51.100.0/24"]}"#,
+            r#"{"mode": "Observed"}"#,
+            r#"{"mode": "networks", "networks": ["198.51.100.0"]}"#,
+            r#"{"mode": "networks", "networks": "198.51.100.0/24"}"#,
+        ] {
+            assert!(json::from_str::<IpPolicy>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn serializes_the_json_policy() {
+        let networks = policy(IpOverride::Networks, &["198.51.100.0/24"]);
+
+        assert_eq!(
+            json::to_string(&networks).unwrap(),
+            r#"{"mode":"networks","networks":["198.51.100.0/24"]}"#
+        );
+    }
+}
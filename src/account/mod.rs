@@ -1,10 +1,32 @@
 mod admin_account;
+pub mod admin_token;
+pub mod hostname;
+pub mod ip_policy;
+pub mod openssh;
 mod password_account;
 mod signing_account;
+pub mod signing_key;
 
-use sea_orm::{DbConn, DbErr};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
 
-pub use admin_account::{ActiveModel as AdminAccountActiveModel, AdminAccount};
+use chrono::{DateTime, Utc};
+use fqdn::FQDN;
+use rocket::{serde::Serialize, FromForm};
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, LikeExpr, Order, Query, SelectStatement, UnionType},
+    ActiveEnum, ColumnTrait, Condition, ConnectionTrait, DbConn, DbErr, EntityTrait,
+    FromQueryResult, PaginatorTrait, QueryFilter, Statement, TransactionTrait, Value,
+};
+
+use crate::{history::AuthMethod, ip_cache::IpCache};
+
+pub use admin_account::{
+    ActiveModel as AdminAccountActiveModel, AdminAccount, AdminRole, AdminSummary, SUPER_ADMIN,
+};
+pub use hostname::HostnamePattern;
 pub use password_account::PasswordAccount;
 pub use signing_account::{PublicKey, SigningAccount};
 
@@ -12,6 +34,15 @@ pub trait Account {
     fn get_domain(&self) -> &str;
 }
 
+/// Why an account couldn't be authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// Missing or wrong credentials
+    Invalid,
+    /// The credentials are valid, but the account is disabled
+    Disabled,
+}
+
 pub async fn exists(domain: &str, db: &DbConn) -> Result<bool, DbErr> {
     if !PasswordAccount::exists(domain, db).await? {
         return SigningAccount::exists(domain, db).await;
@@ -19,3 +50,453 @@ pub async fn exists(domain: &str, db: &DbConn) -> Result<bool, DbErr> {
 
     Ok(true)
 }
+
+/// The domains and hostnames of the accounts matching the SQL `LIKE` pattern
+///
+/// Each name is returned along with the domain of its account.
+async fn names_like(pattern: &str, db: &DbConn) -> Result<Vec<(String, String)>, DbErr> {
+    let mut names: Vec<(String, String)> = Vec::new();
+
+    for domain in password_account::Entity::find()
+        .filter(password_account::Column::Domain.like(pattern))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|m| m.domain)
+        .chain(
+            signing_account::Entity::find()
+                .filter(signing_account::Column::Domain.like(pattern))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|m| m.domain),
+        )
+    {
+        names.push((domain.clone(), domain));
+    }
+
+    names.extend(
+        hostname::find_like(pattern, db)
+            .await?
+            .into_iter()
+            .map(|m| (m.hostname, m.domain)),
+    );
+
+    Ok(names)
+}
+
+/// Whether an account can already update a name matching the pattern
+pub async fn in_use(pattern: &str, db: &DbConn) -> Result<bool, DbErr> {
+    let Ok(pattern) = HostnamePattern::from_str(pattern) else {
+        return Ok(false);
+    };
+
+    // The subtrees, along with the names under the suffix of the pattern
+    let mut candidates = names_like("*.%", db).await?;
+    candidates.extend(names_like(&format!("%{}", pattern.fqdn()), db).await?);
+
+    Ok(candidates.iter().any(|(name, _)| {
+        HostnamePattern::from_str(name).is_ok_and(|existing| existing.overlaps(&pattern))
+    }))
+}
+
+/// The domain of the account able to update the name, through its domain, hostnames or subtrees
+pub async fn owner_of(name: &str, db: &DbConn) -> Result<Option<String>, DbErr> {
+    if exists(name, db).await? {
+        return Ok(Some(name.to_owned()));
+    }
+
+    if let Some(owner) = hostname::owner(name, db).await? {
+        return Ok(Some(owner));
+    }
+
+    let Ok(fqdn) = FQDN::from_str(name) else {
+        return Ok(None);
+    };
+
+    Ok(names_like("*.%", db)
+        .await?
+        .into_iter()
+        .find(|(pattern, _)| HostnamePattern::from_str(pattern).is_ok_and(|p| p.matches(&fqdn)))
+        .map(|(_, domain)| domain))
+}
+
+/// The admin that created the account of the domain, `None` when no account has this domain
+///
+/// The hostnames are attributed to the creator of the account able to update them.
+pub async fn created_by(domain: &str, db: &DbConn) -> Result<Option<String>, DbErr> {
+    let Some(domain) = owner_of(domain, db).await? else {
+        return Ok(None);
+    };
+
+    match PasswordAccount::created_by(&domain, db).await? {
+        None => SigningAccount::created_by(&domain, db).await,
+        created_by => Ok(created_by),
+    }
+}
+
+/// Every hostname and subtree of the account of the domain, starting with the domain itself
+pub async fn hostnames(domain: &str, db: &DbConn) -> Result<Vec<HostnamePattern>, DbErr> {
+    let mut hostnames: Vec<HostnamePattern> =
+        HostnamePattern::from_str(domain).into_iter().collect();
+    hostnames.extend(
+        hostname::find_by_domain(domain, db)
+            .await?
+            .iter()
+            .filter_map(|h| HostnamePattern::from_str(h).ok()),
+    );
+
+    Ok(hostnames)
+}
+
+/// The TTL of the account of the domain, `None` when it uses the default of its zone
+pub async fn ttl(domain: &str, db: &DbConn) -> Result<Option<u32>, DbErr> {
+    match PasswordAccount::ttl(domain, db).await? {
+        None => SigningAccount::ttl(domain, db).await,
+        ttl => Ok(ttl),
+    }
+}
+
+/// Sets the TTL of the account of the domain, `None` going back to the default of its zone
+///
+/// Returns false when no account has this domain.
+pub async fn set_ttl(domain: &str, ttl: Option<u32>, db: &DbConn) -> Result<bool, DbErr> {
+    if !PasswordAccount::set_ttl(domain, ttl, db).await? {
+        return SigningAccount::set_ttl(domain, ttl, db).await;
+    }
+
+    Ok(true)
+}
+
+/// Disables or enables the account of the domain
+///
+/// Returns false when no account has this domain.
+pub async fn set_disabled(domain: &str, disabled: bool, db: &DbConn) -> Result<bool, DbErr> {
+    if !PasswordAccount::set_disabled(domain, disabled, db).await? {
+        return SigningAccount::set_disabled(domain, disabled, db).await;
+    }
+
+    Ok(true)
+}
+
+/// Deletes the account of the domain and its extra hostnames, whichever its type
+///
+/// Nothing is deleted when any of the deletions fails.
+pub async fn delete(domain: &str, db: &DbConn) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    hostname::delete_by_domain(domain, &txn).await?;
+    ip_policy::delete_by_domain(domain, &txn).await?;
+    PasswordAccount::delete(domain, &txn).await?;
+    SigningAccount::delete(domain, &txn).await?;
+    txn.commit().await
+}
+
+/// Filters of the account listing, all optional
+#[derive(FromForm, Default)]
+pub struct AccountFilter {
+    /// Only the accounts authenticating this way
+    #[field(name = "type")]
+    pub auth_type: Option<AuthMethod>,
+    /// Only the accounts created by this admin
+    pub created_by: Option<String>,
+    /// Only the disabled, or the enabled, accounts
+    pub disabled: Option<bool>,
+    /// Only the domains inside this zone
+    pub zone: Option<String>,
+    /// Only the domains containing this text, ignoring case
+    pub search: Option<String>,
+}
+
+impl AccountFilter {
+    /// The database condition for the filters, `None` when the zone filter isn't a valid domain
+    fn condition<C: ColumnTrait>(
+        &self,
+        domain: C,
+        created_by: C,
+        disabled: C,
+    ) -> Option<Condition> {
+        let mut condition = Condition::all();
+
+        if let Some(admin) = &self.created_by {
+            condition = condition.add(created_by.eq(admin));
+        }
+
+        match self.disabled {
+            Some(true) => condition = condition.add(disabled.eq(true)),
+            Some(false) => {
+                condition = condition.add(
+                    Condition::any()
+                        .add(disabled.is_null())
+                        .add(disabled.eq(false)),
+                )
+            }
+            None => {}
+        }
+
+        if let Some(zone) = &self.zone {
+            condition = condition.add(zone_condition(domain, &FQDN::from_str(zone.trim()).ok()?));
+        }
+
+        if let Some(search) = &self.search {
+            condition = condition.add(
+                Expr::expr(Func::lower(Expr::col(domain)))
+                    .like(like(&format!("%{}%", escape_like(&search.to_lowercase())))),
+            );
+        }
+
+        Some(condition)
+    }
+}
+
+/// Escapes the wildcards of the SQL `LIKE` patterns, with `\\` as the escape character
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn like(pattern: &str) -> LikeExpr {
+    LikeExpr::new(pattern).escape('\\')
+}
+
+/// The database condition on the domains, or the suffixes of the subtrees, inside the zone
+fn zone_condition<C: ColumnTrait>(domain: C, zone: &FQDN) -> Condition {
+    let zone = zone.to_string().to_lowercase();
+    let lower = || Expr::expr(Func::lower(Expr::col(domain)));
+
+    Condition::any()
+        .add(lower().eq(zone.as_str()))
+        .add(lower().like(like(&format!("%.{}", escape_like(&zone)))))
+}
+
+/// An account as listed to the admins
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AccountSummary {
+    pub domain: String,
+    #[serde(rename = "type")]
+    pub auth_type: AuthMethod,
+    pub created_by: String,
+    pub disabled: bool,
+    /// The TTL of the records, `None` when they use the default of their zone
+    pub ttl: Option<u32>,
+    /// If the signatures that don't include the IP are accepted, `None` for the password accounts
+    pub legacy_signatures: Option<bool>,
+    /// The last addresses applied to the domain, when known
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+    pub ip_updated_at: Option<DateTime<Utc>>,
+}
+
+impl AccountSummary {
+    fn new(
+        domain: String,
+        auth_type: AuthMethod,
+        created_by: String,
+        disabled: bool,
+        ttl: Option<u32>,
+    ) -> Self {
+        Self {
+            domain,
+            auth_type,
+            created_by,
+            disabled,
+            ttl,
+            legacy_signatures: None,
+            ipv4: None,
+            ipv6: None,
+            ip_updated_at: None,
+        }
+    }
+}
+
+/// A page of the accounts matching the filters, sorted by domain
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AccountPage {
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+    pub accounts: Vec<AccountSummary>,
+}
+
+/// A row of the union of the password and signing accounts
+#[derive(FromQueryResult)]
+struct AccountRow {
+    domain: String,
+    auth_type: AuthMethod,
+    created_by: String,
+    disabled: Option<bool>,
+    ttl: Option<i32>,
+    legacy_signatures: Option<bool>,
+}
+
+impl From<AccountRow> for AccountSummary {
+    fn from(row: AccountRow) -> Self {
+        Self {
+            legacy_signatures: row.legacy_signatures,
+            ..Self::new(
+                row.domain,
+                row.auth_type,
+                row.created_by,
+                row.disabled.unwrap_or(false),
+                row.ttl.map(|ttl| ttl as u32),
+            )
+        }
+    }
+}
+
+/// Lists the accounts matching the filters that the admin can read
+///
+/// The filters, the sorting and the paging are applied by the database.
+pub async fn list(
+    filter: &AccountFilter,
+    admin: &AdminAccount,
+    page: u64,
+    per_page: u64,
+    cache: &IpCache,
+    db: &DbConn,
+) -> Result<AccountPage, DbErr> {
+    let mut queries: Vec<SelectStatement> = Vec::new();
+    let mut total = 0;
+
+    if filter.auth_type.is_none_or(|t| t == AuthMethod::Password) {
+        use password_account::{Column, Entity};
+
+        if let Some(condition) =
+            filter.condition(Column::Domain, Column::CreatedBy, Column::Disabled)
+        {
+            let condition = condition.add(admin.read_condition(Column::Domain, Column::CreatedBy));
+            total += Entity::find().filter(condition.clone()).count(db).await?;
+            queries.push(
+                Query::select()
+                    .column(Column::Domain)
+                    .expr_as(
+                        Expr::val(AuthMethod::Password.to_value()),
+                        Alias::new("auth_type"),
+                    )
+                    .columns([Column::CreatedBy, Column::Disabled, Column::Ttl])
+                    .expr_as(
+                        Expr::val(Value::Bool(None)),
+                        Alias::new("legacy_signatures"),
+                    )
+                    .from(Entity)
+                    .cond_where(condition)
+                    .to_owned(),
+            );
+        }
+    }
+    if filter.auth_type.is_none_or(|t| t == AuthMethod::Signing) {
+        use signing_account::{Column, Entity};
+
+        if let Some(condition) =
+            filter.condition(Column::Domain, Column::CreatedBy, Column::Disabled)
+        {
+            let condition = condition.add(admin.read_condition(Column::Domain, Column::CreatedBy));
+            total += Entity::find().filter(condition.clone()).count(db).await?;
+            queries.push(
+                Query::select()
+                    .column(Column::Domain)
+                    .expr_as(
+                        Expr::val(AuthMethod::Signing.to_value()),
+                        Alias::new("auth_type"),
+                    )
+                    .columns([Column::CreatedBy, Column::Disabled, Column::Ttl])
+                    // Null means the legacy signatures are accepted
+                    .expr_as(
+                        Expr::col(Column::LegacySignatures).if_null(true),
+                        Alias::new("legacy_signatures"),
+                    )
+                    .from(Entity)
+                    .cond_where(condition)
+                    .to_owned(),
+            );
+        }
+    }
+
+    let mut queries = queries.into_iter();
+    let mut accounts: Vec<AccountSummary> = match queries.next() {
+        None => Vec::new(),
+        Some(mut query) => {
+            for other in queries {
+                query.union(UnionType::All, other);
+            }
+            query
+                .order_by(Alias::new("domain"), Order::Asc)
+                .limit(per_page)
+                .offset(page.saturating_sub(1) * per_page);
+
+            let statement: Statement = db.get_database_backend().build(&query);
+            AccountRow::find_by_statement(statement)
+                .all(db)
+                .await?
+                .into_iter()
+                .map(AccountSummary::from)
+                .collect()
+        }
+    };
+
+    for account in accounts.iter_mut() {
+        if let Some(cached) = cache.get(&account.domain) {
+            account.ipv4 = cached.addresses.ipv4;
+            account.ipv6 = cached.addresses.ipv6;
+            account.ip_updated_at = Some(cached.updated_at);
+        }
+    }
+
+    Ok(AccountPage {
+        page,
+        per_page,
+        total,
+        accounts,
+    })
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use migration::{Migrator, MigratorTrait};
+    use sea_orm::{ActiveModelTrait, Database, Set};
+
+    use super::*;
+
+    async fn database() -> DbConn {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        password_account::ActiveModel {
+            domain: Set(String::from("a.dyn.example.com")),
+            created_by: Set(String::from(SUPER_ADMIN)),
+            disabled: Set(Some(false)),
+            ttl: Set(None),
+            password_hash: Set(String::new()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        hostname::add("a.dyn.example.com", "*.sub.dyn.example.com", &db)
+            .await
+            .unwrap();
+
+        db
+    }
+
+    #[test]
+    fn in_use_finds_overlapping_names() {
+        rocket::async_test(async {
+            let db = database().await;
+
+            for (pattern, expected) in [
+                ("a.dyn.example.com", true),
+                ("b.dyn.example.com", false),
+                ("*.dyn.example.com", true),
+                ("*.a.dyn.example.com", false),
+                ("x.sub.dyn.example.com", true),
+                ("sub.dyn.example.com", false),
+                ("*.y.sub.dyn.example.com", true),
+                ("*.xsub.dyn.example.com", false),
+            ] {
+                assert_eq!(in_use(pattern, &db).await.unwrap(), expected, "{pattern}");
+            }
+        })
+    }
+}
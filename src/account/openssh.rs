@@ -0,0 +1,382 @@
+/// OpenSSH public keys and `ssh-keygen -Y sign` signatures for the signing accounts
+///
+/// The OpenSSH keys are converted to regular public keys when uploaded, so they are stored
+/// and fingerprinted like the PEM ones.
+use base64::prelude::*;
+use openssl::{
+    bn::BigNum,
+    ec::{EcGroup, EcKey, EcPoint},
+    ecdsa::EcdsaSig,
+    hash::{hash, MessageDigest},
+    nid::Nid,
+    pkey::{Id, PKey, PKeyRef, Public},
+    rsa::Rsa,
+    sign::Verifier,
+};
+
+use super::PublicKey;
+
+/// Namespace the signatures must be made for, given to `ssh-keygen -Y sign -n`
+pub const SSHSIG_NAMESPACE: &str = "ft-ddns";
+
+/// Magic preamble of the SSHSIG signatures and of the data they sign
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+const ARMOR_BEGIN: &str = "-----BEGIN SSH SIGNATURE-----";
+const ARMOR_END: &str = "-----END SSH SIGNATURE-----";
+
+/// Reader of the SSH wire encoding, made of big-endian lengths followed by their bytes
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn mpint(&mut self) -> Option<BigNum> {
+        BigNum::from_slice(self.string()?).ok()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+/// The curve and the digest of an OpenSSH ECDSA key type
+fn ecdsa_curve(name: &[u8]) -> Option<(Nid, MessageDigest)> {
+    match name {
+        b"nistp256" => Some((Nid::X9_62_PRIME256V1, MessageDigest::sha256())),
+        b"nistp384" => Some((Nid::SECP384R1, MessageDigest::sha384())),
+        b"nistp521" => Some((Nid::SECP521R1, MessageDigest::sha512())),
+        _ => None,
+    }
+}
+
+/// Whether the upload looks like an OpenSSH public key line rather than a PEM key
+pub fn is_public_key_line(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with("ssh-") || text.starts_with("ecdsa-sha2-")
+}
+
+/// Parses an OpenSSH public key line, such as the content of `ssh_host_ed25519_key.pub`
+///
+/// Supports the `ssh-ed25519`, `ecdsa-sha2-nistp*` and `ssh-rsa` keys, the comment is ignored.
+pub fn parse_public_key(line: &str) -> Option<PublicKey> {
+    let mut fields = line.split_whitespace();
+    let key_type = fields.next()?;
+    let blob = BASE64_STANDARD.decode(fields.next()?).ok()?;
+
+    let mut reader = Reader(&blob);
+    if reader.string()? != key_type.as_bytes() {
+        return None;
+    }
+
+    let key = match key_type {
+        "ssh-ed25519" => PKey::public_key_from_raw_bytes(reader.string()?, Id::ED25519).ok()?,
+        "ssh-rsa" => {
+            let e = reader.mpint()?;
+            let n = reader.mpint()?;
+            PKey::from_rsa(Rsa::from_public_components(n, e).ok()?).ok()?
+        }
+        _ => {
+            let curve = key_type.strip_prefix("ecdsa-sha2-")?;
+            if reader.string()? != curve.as_bytes() {
+                return None;
+            }
+
+            let (nid, _) = ecdsa_curve(curve.as_bytes())?;
+            let group = EcGroup::from_curve_name(nid).ok()?;
+            let mut ctx = openssl::bn::BigNumContext::new().ok()?;
+            let point = EcPoint::from_bytes(&group, reader.string()?, &mut ctx).ok()?;
+            PKey::from_ec_key(EcKey::from_public_key(&group, &point).ok()?).ok()?
+        }
+    };
+
+    reader.is_empty().then_some(key)
+}
+
+/// Removes the armor and the line breaks of an SSHSIG signature, leaving its Base64 encoding
+///
+/// The signature can't hold line breaks in an HTTP header, so they are accepted as spaces or removed.
+pub fn unarmor(signature: &str) -> String {
+    signature
+        .replace(ARMOR_BEGIN, "")
+        .replace(ARMOR_END, "")
+        .split_whitespace()
+        .collect()
+}
+
+/// Whether the signature is an SSHSIG blob rather than a raw signature
+pub fn is_sshsig(signature: &[u8]) -> bool {
+    signature.starts_with(SSHSIG_MAGIC)
+}
+
+/// Whether the SSHSIG signature of the message is valid for the key, in the namespace of the service
+pub fn verify_sshsig(key: &PKeyRef<Public>, message: &[u8], signature: &[u8]) -> Option<bool> {
+    let mut reader = Reader(signature.strip_prefix(SSHSIG_MAGIC)?);
+    if reader.u32()? != 1 {
+        return None;
+    }
+
+    // The key that made the signature, the keys of the account verify it anyway
+    let signer = reader.string()?;
+    let namespace = reader.string()?;
+    let reserved = reader.string()?;
+    let hash_algorithm = reader.string()?;
+    let mut signature = Reader(reader.string()?);
+
+    if namespace != SSHSIG_NAMESPACE.as_bytes() {
+        log::debug!("SSHSIG signature made for another namespace");
+        return Some(false);
+    }
+
+    let message_digest = match hash_algorithm {
+        b"sha256" => MessageDigest::sha256(),
+        b"sha512" => MessageDigest::sha512(),
+        _ => return None,
+    };
+
+    let mut signed = SSHSIG_MAGIC.to_vec();
+    put_string(&mut signed, namespace);
+    put_string(&mut signed, reserved);
+    put_string(&mut signed, hash_algorithm);
+    put_string(&mut signed, &hash(message_digest, message).ok()?);
+
+    let signer_type = Reader(signer).string()?;
+    let signature_type = signature.string()?;
+    let signature_bytes = signature.string()?;
+
+    let (mut verifier, signature_bytes) = match (key.id(), signature_type) {
+        (Id::ED25519, b"ssh-ed25519") => (
+            Verifier::new_without_digest(key).ok()?,
+            signature_bytes.to_vec(),
+        ),
+        (Id::RSA, b"rsa-sha2-256") => (
+            Verifier::new(MessageDigest::sha256(), key).ok()?,
+            signature_bytes.to_vec(),
+        ),
+        (Id::RSA, b"rsa-sha2-512") => (
+            Verifier::new(MessageDigest::sha512(), key).ok()?,
+            signature_bytes.to_vec(),
+        ),
+        (Id::EC, _) => {
+            let curve = signature_type.strip_prefix(b"ecdsa-sha2-")?;
+            if signer_type != signature_type {
+                return Some(false);
+            }
+
+            let (_, digest) = ecdsa_curve(curve)?;
+            let mut rs = Reader(signature_bytes);
+            let der = EcdsaSig::from_private_components(rs.mpint()?, rs.mpint()?)
+                .ok()?
+                .to_der()
+                .ok()?;
+            (Verifier::new(digest, key).ok()?, der)
+        }
+        _ => return Some(false),
+    };
+
+    verifier.verify_oneshot(&signature_bytes, &signed).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message of a version 1 signature, `{date};{domain}`
+    const MESSAGE: &[u8] = b"2026-10-17 10:00:00+00:00;device.dyn.example.com";
+
+    // Made with `ssh-keygen -Y sign -n ft-ddns` on the message above
+    const ED25519_KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJRnYqlmfga+vNb7VTqDf4UqmSvrkViv5RKKXviWPAXs ed25519@ft-ddns";
+    const RSA_KEY: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDMgupP3dctlhgYV7H/Ees3afjZTRClSPUgZ3a79MCS56cjok4lrV0sDmmka0YrnCfsaGSUovZ0aF4p3ISOnaFfBbl6XC4BdTr9a1yovlLR4Vf5S8gvpcHakW4JkysrzKXyzWNF7fFSVLs1m81Yk6kxkVRGUI2WxJiZPiyICmGU1cZ+lgI62FQmj9VyVZ98q1H8VkqihRzVS+9KGv5orMwbYbpmiR2fEt+3aemmVjE21af1yqxPfXfVvFuwglpLqNTjLLg1vj7M3f/9uuhx2hw/DX7WdWOhwQ7Lky3Gxgs+/vFe0NFymQ21xPpKeqo59hq5eebjus4N17xMYY3yvOXl rsa@ft-ddns";
+    const P256_KEY: &str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBOspholAMBaB4Wa/PSVTmd0toh2GCiXeV5sQdP4J+24RrQrxGBgngetOG605M+IbGgpQtQFvmhSgfWyL1s9u3u0= p256@ft-ddns";
+    const P384_KEY: &str = "ecdsa-sha2-nistp384 AAAAE2VjZHNhLXNoYTItbmlzdHAzODQAAAAIbmlzdHAzODQAAABhBMFCBeNrR84kv75zRUVOlJX11DllEbczk0d8HMELE/PlSAS8LBEj6S0gfhy3mxY6hPP0AIqyh71OY/O0ZaJJsFzGKRjxcrvYxkbiKSiwKJ2zHV6h/Yaw/HCNEVMcCPhIgQ== p384@ft-ddns";
+    const ED25519_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAglGdiqWZ+Br681vtVOoN/hSqZK+\n\
+uRWK/lEope+JY8BewAAAAHZnQtZGRucwAAAAAAAAAGc2hhNTEyAAAAUwAAAAtzc2gtZWQy\n\
+NTUxOQAAAEA0IUQtkyxLYOotmAc8YdBD5garrjGCavgbWFLgtuKxR5LI6BtB6yW/8xVNyt\n\
+k7IKg7BYZHfd4kBT2lbNefqksO\n\
+-----END SSH SIGNATURE-----";
+    const RSA_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAARcAAAAHc3NoLXJzYQAAAAMBAAEAAAEBAMyC6k/d1y2WGBhXsf8R6z\n\
+dp+NlNEKVI9SBndrv0wJLnpyOiTiWtXSwOaaRrRiucJ+xoZJSi9nRoXinchI6doV8FuXpc\n\
+LgF1Ov1rXKi+UtHhV/lLyC+lwdqRbgmTKyvMpfLNY0Xt8VJUuzWbzViTqTGRVEZQjZbEmJ\n\
+k+LIgKYZTVxn6WAjrYVCaP1XJVn3yrUfxWSqKFHNVL70oa/miszBthumaJHZ8S37dp6aZW\n\
+MTbVp/XKrE99d9W8W7CCWkuo1OMsuDW+Pszd//266HHaHD8NftZ1Y6HBDsuTLcbGCz7+8V\n\
+7Q0XKZDbXE+kp6qjn2Grl55uO6zg3XvExhjfK85eUAAAAHZnQtZGRucwAAAAAAAAAGc2hh\n\
+NTEyAAABFAAAAAxyc2Etc2hhMi01MTIAAAEAfxakQn2f+bukCbPJA6mNfRLrLsvGozrWhD\n\
+odj8kUFmj0Qelm0AjU1O7NBf0ovd0ko+Nu5d69mVkJDegj1mV3PSNgEsDeTWrwlaJyrf3f\n\
+MLSkoA5j4FXso5S0hypIc2eocbQmM4YU0mihIsCqhjgM2vDKRLzUocfbW9HeMqV1zQlELr\n\
+LRXrTeq1ZQJDV14zBZvVFU3plqzX+ri4vDq944luHPWZL4NU/m+KS2ZmFpYjf0XeXn3JIP\n\
+ap1LIEH2Riif8YlLuTSeGT9aoA0J7cc1Dmjw1TXox0OGHKx3RTtcmnuVj46tRyqLYIhJHS\n\
+BKybWpjFBLYhjvNm2XyQC2LDe1PQ==\n\
+-----END SSH SIGNATURE-----";
+    const P256_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAAGgAAAATZWNkc2Etc2hhMi1uaXN0cDI1NgAAAAhuaXN0cDI1NgAAAE\n\
+EE6ymGiUAwFoHhZr89JVOZ3S2iHYYKJd5XmxB0/gn7bhGtCvEYGCeB604brTkz4hsaClC1\n\
+AW+aFKB9bIvWz27e7QAAAAdmdC1kZG5zAAAAAAAAAAZzaGE1MTIAAABlAAAAE2VjZHNhLX\n\
+NoYTItbmlzdHAyNTYAAABKAAAAIQDzcxmJEYOvynWRnE1NgP0HRhZHILNxsU2jZvBI9PWA\n\
+3gAAACEAwaQqOsPxq5KSbM7dETm0+JOjROxWqbE6JhCVBDRfUk8=\n\
+-----END SSH SIGNATURE-----";
+    const P384_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAAIgAAAATZWNkc2Etc2hhMi1uaXN0cDM4NAAAAAhuaXN0cDM4NAAAAG\n\
+EEwUIF42tHziS/vnNFRU6UlfXUOWURtzOTR3wcwQsT8+VIBLwsESPpLSB+HLebFjqE8/QA\n\
+irKHvU5j87RlokmwXMYpGPFyu9jGRuIpKLAonbMdXqH9hrD8cI0RUxwI+EiBAAAAB2Z0LW\n\
+RkbnMAAAAAAAAABnNoYTUxMgAAAIQAAAATZWNkc2Etc2hhMi1uaXN0cDM4NAAAAGkAAAAx\n\
+AIefcPJwPvHfQkmtODjkjiev7kIPI8lWmWAD0Pzf1VdmsMByZgLwGUF8iEuYCbVc4wAAAD\n\
+Be1A3tYmHP/bZS9Do/CrDOq3dLk+cPUPtXpf7ejxyojjus/LExq7lVX616D6cKJsI=\n\
+-----END SSH SIGNATURE-----";
+    /// Signed with `-n other`
+    const NS_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAglGdiqWZ+Br681vtVOoN/hSqZK+\n\
+uRWK/lEope+JY8BewAAAAFb3RoZXIAAAAAAAAABnNoYTUxMgAAAFMAAAALc3NoLWVkMjU1\n\
+MTkAAABAsESfGxouVD3bcOOLlhXbbG9zlXjEfHftPILHtKV44XcsWXdzf0TTliAm2hBepI\n\
+7aQNt8HHWDC4/kdZEZILyMAw==\n\
+-----END SSH SIGNATURE-----";
+    /// Signed with `-O hashalg=sha256`
+    const SHA256_SIG: &str = "\
+-----BEGIN SSH SIGNATURE-----\n\
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAglGdiqWZ+Br681vtVOoN/hSqZK+\n\
+uRWK/lEope+JY8BewAAAAHZnQtZGRucwAAAAAAAAAGc2hhMjU2AAAAUwAAAAtzc2gtZWQy\n\
+NTUxOQAAAECxmcvdk4CDz2/asD/70+tH+4WzAkY4wqdaDFwIGyBQqCCM7DD0Y3Gfq3ycZO\n\
+eFpChKheUdOuFq977qVgSd7egN\n\
+-----END SSH SIGNATURE-----";
+
+    fn sshsig(armored: &str) -> Vec<u8> {
+        BASE64_STANDARD.decode(unarmor(armored)).unwrap()
+    }
+
+    fn verify(key: &str, armored: &str) -> Option<bool> {
+        verify_sshsig(&parse_public_key(key).unwrap(), MESSAGE, &sshsig(armored))
+    }
+
+    /// Replaces a string field of the SSHSIG blob by another one of the same length
+    fn replace_field(blob: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+        let at = blob.windows(from.len()).position(|w| w == from).unwrap();
+        [&blob[..at], to, &blob[at + from.len()..]].concat()
+    }
+
+    #[test]
+    fn parses_public_keys() {
+        let key_types = [
+            (ED25519_KEY, Id::ED25519),
+            (RSA_KEY, Id::RSA),
+            (P256_KEY, Id::EC),
+            (P384_KEY, Id::EC),
+        ];
+
+        for (line, id) in key_types {
+            assert!(is_public_key_line(line));
+            assert_eq!(
+                parse_public_key(line).map(|key| key.id()),
+                Some(id),
+                "{line}"
+            );
+        }
+        assert_eq!(parse_public_key(RSA_KEY).unwrap().bits(), 2048);
+    }
+
+    #[test]
+    fn refuses_trailing_bytes_after_the_key_blob() {
+        let mut fields = ED25519_KEY.split_whitespace();
+        let key_type = fields.next().unwrap();
+        let mut blob = BASE64_STANDARD.decode(fields.next().unwrap()).unwrap();
+        blob.push(0);
+
+        let line = format!("{key_type} {}", BASE64_STANDARD.encode(blob));
+        assert!(parse_public_key(&line).is_none());
+    }
+
+    #[test]
+    fn refuses_mismatched_key_types() {
+        let blob = ED25519_KEY.split_whitespace().nth(1).unwrap();
+
+        assert!(parse_public_key(&format!("ssh-rsa {blob}")).is_none());
+    }
+
+    #[test]
+    fn verifies_ssh_keygen_signatures() {
+        assert_eq!(verify(ED25519_KEY, ED25519_SIG), Some(true));
+        assert_eq!(verify(RSA_KEY, RSA_SIG), Some(true));
+        assert_eq!(verify(P256_KEY, P256_SIG), Some(true));
+        assert_eq!(verify(P384_KEY, P384_SIG), Some(true));
+        assert_eq!(verify(ED25519_KEY, SHA256_SIG), Some(true));
+    }
+
+    #[test]
+    fn refuses_signatures_of_other_messages_or_keys() {
+        let key = parse_public_key(ED25519_KEY).unwrap();
+
+        assert_eq!(
+            verify_sshsig(
+                &key,
+                b"2026-10-17 10:00:01+00:00;device.dyn.example.com",
+                &sshsig(ED25519_SIG)
+            ),
+            Some(false)
+        );
+        assert_eq!(verify(ED25519_KEY, P256_SIG), Some(false));
+        assert_eq!(verify(P384_KEY, P256_SIG), Some(false));
+    }
+
+    #[test]
+    fn refuses_other_namespaces() {
+        assert_eq!(verify(ED25519_KEY, NS_SIG), Some(false));
+    }
+
+    #[test]
+    fn refuses_unknown_hash_algorithms() {
+        let key = parse_public_key(ED25519_KEY).unwrap();
+        let blob = replace_field(&sshsig(ED25519_SIG), b"sha512", b"sha384");
+
+        assert_eq!(verify_sshsig(&key, MESSAGE, &blob), None);
+    }
+
+    #[test]
+    fn refuses_truncated_signatures() {
+        let key = parse_public_key(ED25519_KEY).unwrap();
+        let blob = sshsig(ED25519_SIG);
+
+        // Cut inside the length prefix of the public key
+        assert_eq!(verify_sshsig(&key, MESSAGE, &blob[..12]), None);
+        assert_eq!(verify_sshsig(&key, MESSAGE, &blob[..blob.len() - 1]), None);
+        assert_eq!(Reader(&[0, 0, 1]).u32(), None);
+        assert_eq!(Reader(&[0, 0, 0, 4, 1, 2]).string(), None);
+    }
+
+    #[test]
+    fn unarmors_signatures_sent_on_one_line() {
+        let one_line = ED25519_SIG.replace('\n', " ");
+
+        assert_eq!(unarmor(&one_line), unarmor(ED25519_SIG));
+        assert!(!unarmor(&one_line).contains(' '));
+        assert_eq!(verify(ED25519_KEY, &one_line), Some(true));
+        assert!(is_sshsig(&sshsig(&one_line)));
+    }
+}
@@ -9,9 +9,13 @@ use rocket::{
 use rocket_basicauth::BasicAuth;
 use sea_orm::{entity::prelude::*, Set};
 
-use crate::{account::AdminAccount, utils::compare_with_hash};
+use crate::{
+    account::AdminAccount,
+    history::{record_rejection, AuthMethod},
+    utils::compare_with_hash,
+};
 
-use super::Account;
+use super::{Account, AuthError};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "password_account")]
@@ -29,6 +33,10 @@ pub struct Model {
     /// Must be true to disable the account
     /// Null or false means the account is enabled
     pub disabled: Option<bool>,
+    /// The TTL of the records of the account, in seconds
+    ///
+    /// Null means the records use the default TTL of their zone
+    pub ttl: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -49,6 +57,14 @@ impl PasswordAccount {
         &self.0
     }
 
+    /// The admin that created the account of the domain, `None` when it doesn't exist
+    pub async fn created_by(domain: &str, db: &DbConn) -> Result<Option<String>, DbErr> {
+        Ok(Entity::find_by_id(domain)
+            .one(db)
+            .await?
+            .map(|account| account.created_by))
+    }
+
     pub async fn exists(domain: &str, db: &DbConn) -> Result<bool, DbErr> {
         Entity::find()
             .filter(Column::Domain.eq(domain))
@@ -57,16 +73,69 @@ impl PasswordAccount {
             .map(|v| v.is_some())
     }
 
+    /// Replaces the password hash of the account, returning false when it doesn't exist
+    pub async fn set_password_hash(
+        domain: &str,
+        password_hash: &str,
+        db: &DbConn,
+    ) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::PasswordHash, Expr::value(password_hash))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    /// The TTL of the account, `None` when it doesn't exist or uses the default of its zone
+    pub async fn ttl(domain: &str, db: &DbConn) -> Result<Option<u32>, DbErr> {
+        Ok(Entity::find_by_id(domain)
+            .one(db)
+            .await?
+            .and_then(|account| account.ttl)
+            .map(|ttl| ttl as u32))
+    }
+
+    /// Replaces the TTL of the account, returning false when it doesn't exist
+    pub async fn set_ttl(domain: &str, ttl: Option<u32>, db: &DbConn) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Ttl, Expr::value(ttl.map(|ttl| ttl as i32)))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    pub async fn set_disabled(domain: &str, disabled: bool, db: &DbConn) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Disabled, Expr::value(disabled))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    pub async fn delete(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+        Entity::delete_many()
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn create_account(
         domain: &str,
         password_hash: &str,
         created_by: &AdminAccount,
+        ttl: Option<u32>,
         db: &DbConn,
     ) -> Result<(), DbErr> {
         let account = ActiveModel {
             disabled: Set(Some(false)),
             domain: Set(domain.to_owned()),
             created_by: Set(created_by.get_user().to_string()),
+            ttl: Set(ttl.map(|ttl| ttl as i32)),
             password_hash: Set(password_hash.to_owned()),
         };
 
@@ -78,19 +147,30 @@ impl PasswordAccount {
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for PasswordAccount {
-    type Error = ();
+    type Error = AuthError;
 
-    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, AuthError> {
         let db: &DbConn = request.rocket().state::<DbConn>().unwrap();
+        let headers = request.headers();
         let auth: BasicAuth;
-        match request.guard::<BasicAuth>().await {
-            request::Outcome::Error(_) => {
-                return request::Outcome::Error((http::Status::BadRequest, ()))
-            }
-            request::Outcome::Forward(_) => {
-                return request::Outcome::Error((http::Status::BadRequest, ()))
+        // The acme-dns clients send the credentials in their own headers
+        if let (Some(username), Some(password)) =
+            (headers.get_one("X-Api-User"), headers.get_one("X-Api-Key"))
+        {
+            auth = BasicAuth {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            };
+        } else {
+            match request.guard::<BasicAuth>().await {
+                request::Outcome::Error(_) => {
+                    return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid))
+                }
+                request::Outcome::Forward(_) => {
+                    return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid))
+                }
+                request::Outcome::Success(a) => auth = a,
             }
-            request::Outcome::Success(a) => auth = a,
         }
 
         let account: Option<Model> = Entity::find()
@@ -100,9 +180,19 @@ impl<'r> FromRequest<'r> for PasswordAccount {
             .unwrap();
 
         match account {
-            None => request::Outcome::Error((http::Status::Unauthorized, ())),
+            None => request::Outcome::Error((http::Status::Unauthorized, AuthError::Invalid)),
             Some(d) => match compare_with_hash(&auth.password, &d.password_hash) {
-                Err(_) => request::Outcome::Error((http::Status::Unauthorized, ())),
+                Err(_) => {
+                    record_rejection(request, &d.domain, AuthMethod::Password, "Invalid password")
+                        .await;
+                    request::Outcome::Error((http::Status::Unauthorized, AuthError::Invalid))
+                }
+                Ok(()) if d.disabled == Some(true) => {
+                    log::warn!("The disabled account {} attempted an update", d.domain);
+                    record_rejection(request, &d.domain, AuthMethod::Password, "Account disabled")
+                        .await;
+                    request::Outcome::Error((http::Status::Forbidden, AuthError::Disabled))
+                }
                 Ok(()) => request::Outcome::Success(PasswordAccount(d.domain)),
             },
         }
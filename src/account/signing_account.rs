@@ -6,20 +6,30 @@ use std::net::IpAddr;
 /// Requires setting up with a keypair where the public key is stored in the database.
 use base64::prelude::*;
 use chrono::Utc;
-use openssl::hash::MessageDigest;
 use openssl::pkey::PKeyRef;
 use openssl::pkey::Public;
-use openssl::sign::Verifier;
 use rocket::{
     http,
     request::{self, FromRequest},
     Request,
 };
-use sea_orm::{entity::prelude::*, Set};
+use sea_orm::{entity::prelude::*, Set, TransactionTrait};
 
-use crate::account::AdminAccount;
+use crate::{
+    account::AdminAccount,
+    history::{record_rejection, AuthMethod},
+    ip::Addresses,
+    replay::ReplayCache,
+};
+
+use super::{openssh, signing_key, Account, AuthError};
 
-use super::Account;
+/// Number of seconds a signature date can be away from the time of the server
+const SIGNATURE_TIME_MARGIN: i64 = 60;
+/// Longest nonce accepted in the `Ftddns-Nonce` header
+const MAX_NONCE_LENGTH: usize = 128;
+/// Query parameters covered by the version 2 signatures, in their order in the signed string
+const SIGNED_PARAMS: [&str; 2] = ["hostname", "value"];
 
 /// Type public key
 pub type PublicKey = openssl::pkey::PKey<Public>;
@@ -31,9 +41,6 @@ pub struct Model {
     /// The domain name that the account can overwrite
     /// Also serves as the username for the account
     pub domain: String,
-    /// The public key of the account
-    /// Used to verify the signature of the requests
-    pub public_key: String,
     /// The admin that created the account
     pub created_by: String,
     /// If the account is disabled
@@ -41,6 +48,15 @@ pub struct Model {
     /// Must be true to disable the account
     /// Null or false means the account is enabled
     pub disabled: Option<bool>,
+    /// The TTL of the records of the account, in seconds
+    ///
+    /// Null means the records use the default TTL of their zone
+    pub ttl: Option<i32>,
+    /// If the signatures that don't include the IP are accepted
+    ///
+    /// Must be false to only accept the version 2 signatures
+    /// Null or true means both versions are accepted
+    pub legacy_signatures: Option<bool>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -48,7 +64,10 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
-pub struct SigningAccount(String);
+/// An authenticated signing account, along with the addresses it signed
+///
+/// The addresses are only known for the version 2 signatures.
+pub struct SigningAccount(String, Option<Addresses>);
 
 impl Account for SigningAccount {
     fn get_domain(&self) -> &str {
@@ -57,6 +76,19 @@ impl Account for SigningAccount {
 }
 
 impl SigningAccount {
+    /// The addresses covered by the signature, which the records must receive
+    pub fn signed_addresses(&self) -> Option<Addresses> {
+        self.1
+    }
+
+    /// The admin that created the account of the domain, `None` when it doesn't exist
+    pub async fn created_by(domain: &str, db: &DbConn) -> Result<Option<String>, DbErr> {
+        Ok(Entity::find_by_id(domain)
+            .one(db)
+            .await?
+            .map(|account| account.created_by))
+    }
+
     pub async fn exists(domain: &str, db: &DbConn) -> Result<bool, DbErr> {
         Entity::find()
             .filter(Column::Domain.eq(domain))
@@ -65,115 +97,398 @@ impl SigningAccount {
             .map(|v| v.is_some())
     }
 
+    /// Revokes every key of the account and adds this one, returning false when it doesn't exist
+    pub async fn replace_keys(
+        domain: &str,
+        pub_key: &PKeyRef<Public>,
+        db: &DbConn,
+    ) -> Result<bool, DbErr> {
+        if !Self::exists(domain, db).await? {
+            return Ok(false);
+        }
+
+        let txn = db.begin().await?;
+        signing_key::revoke_all(domain, &txn).await?;
+        signing_key::add(domain, pub_key, None, None, &txn).await?;
+        txn.commit().await?;
+
+        Ok(true)
+    }
+
+    /// The TTL of the account, `None` when it doesn't exist or uses the default of its zone
+    pub async fn ttl(domain: &str, db: &DbConn) -> Result<Option<u32>, DbErr> {
+        Ok(Entity::find_by_id(domain)
+            .one(db)
+            .await?
+            .and_then(|account| account.ttl)
+            .map(|ttl| ttl as u32))
+    }
+
+    /// Replaces the TTL of the account, returning false when it doesn't exist
+    pub async fn set_ttl(domain: &str, ttl: Option<u32>, db: &DbConn) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Ttl, Expr::value(ttl.map(|ttl| ttl as i32)))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    /// Accepts or refuses the signatures that don't include the IP, returning false when it doesn't exist
+    pub async fn set_legacy_signatures(
+        domain: &str,
+        allowed: bool,
+        db: &DbConn,
+    ) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::LegacySignatures, Expr::value(allowed))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    pub async fn set_disabled(domain: &str, disabled: bool, db: &DbConn) -> Result<bool, DbErr> {
+        Entity::update_many()
+            .col_expr(Column::Disabled, Expr::value(disabled))
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await
+            .map(|r| r.rows_affected > 0)
+    }
+
+    /// Deletes the account and its keys, must run inside a transaction
+    pub async fn delete(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+        signing_key::delete_by_domain(domain, db).await?;
+        Entity::delete_many()
+            .filter(Column::Domain.eq(domain))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn create_account(
         domain: &str,
         pub_key: &PKeyRef<Public>,
         created_by: &AdminAccount,
+        ttl: Option<u32>,
         db: &DbConn,
     ) -> Result<(), DbErr> {
         let signature_account = ActiveModel {
             disabled: Set(Some(false)),
             domain: Set(domain.to_owned()),
             created_by: Set(created_by.get_user().to_string()),
-            public_key: Set(String::from_utf8(pub_key.public_key_to_pem().unwrap()).unwrap()),
+            ttl: Set(ttl.map(|ttl| ttl as i32)),
+            legacy_signatures: Set(Some(true)),
         };
 
-        signature_account.insert(db).await?;
+        let txn = db.begin().await?;
+        signature_account.insert(&txn).await?;
+        signing_key::add(domain, pub_key, None, None, &txn).await?;
+        txn.commit().await?;
 
         Ok(())
     }
 
-    pub async fn find_key_by_domain(domain: &str, db: &DbConn) -> Result<Option<PublicKey>, DbErr> {
-        Entity::find()
+    /// Finds the active public keys of the domain, along with its account
+    pub async fn find_keys_by_domain(
+        domain: &str,
+        db: &DbConn,
+    ) -> Result<Option<(Vec<PublicKey>, Model)>, DbErr> {
+        let Some(account) = Entity::find()
             .filter(Column::Domain.eq(domain))
             .one(db)
-            .await
-            .map(|r| r.map(|m| PublicKey::public_key_from_pem(m.public_key.as_bytes()).unwrap()))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let keys = signing_key::find_active(domain, db)
+            .await?
+            .iter()
+            .filter_map(signing_key::Model::public_key)
+            .collect();
+
+        Ok(Some((keys, account)))
+    }
+}
+
+/// The fields covered by the version 2 signatures, between the domain and the nonce
+struct V2Fields<'a> {
+    /// The addresses as sent in the `Ftddns-Ip` header, or the IP of the client
+    addresses: &'a str,
+    /// The method and the path of the request, separated by a space
+    route: &'a str,
+    /// The values of the `SIGNED_PARAMS`, empty when absent
+    params: &'a [&'a str],
+}
+
+/// The addresses covered by a version 2 signature, as signed and as parsed
+///
+/// Without the `Ftddns-Ip` header, the signature covers the IP of the client.
+fn v2_addresses(header: Option<&str>, client: IpAddr) -> Result<(String, Addresses), ()> {
+    match header {
+        Some(list) => Addresses::from_list(list).map(|addresses| (list.to_owned(), addresses)),
+        None => {
+            let client = client.to_canonical();
+            Ok((client.to_string(), Addresses::from(client)))
+        }
     }
 }
 
+/// The string signed by the client, of version 1 without the version 2 fields
+fn signed_message(date: &str, domain: &str, v2: Option<V2Fields>, nonce: Option<&str>) -> String {
+    let mut message = match v2 {
+        Some(fields) => format!(
+            "v2;{date};{domain};{};{};{}",
+            fields.addresses,
+            fields.route,
+            fields.params.join(";")
+        ),
+        None => format!("{date};{domain}"),
+    };
+    if let Some(nonce) = nonce {
+        message.push(';');
+        message.push_str(nonce);
+    }
+
+    message
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for SigningAccount {
-    type Error = ();
+    type Error = AuthError;
 
-    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, AuthError> {
         let db: &DbConn = request.rocket().state::<DbConn>().unwrap();
         let ip: IpAddr = if let Some(ip) = request.client_ip() {
             ip
         } else {
             log::warn!("Request had no IP.");
-            return request::Outcome::Error((http::Status::BadRequest, ()));
+            return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
         };
 
         let headers = request.headers();
         let date = headers.get_one("Ftddns-Date");
         let domain = headers.get_one("Ftddns-Domain").map(String::from);
         let signature = headers.get_one("Ftddns-Signature");
+        let nonce = headers.get_one("Ftddns-Nonce");
+        let version = headers.get_one("Ftddns-Version").unwrap_or("1");
+        let signed_ip = headers.get_one("Ftddns-Ip").map(str::trim);
 
-        let (date_str, domain, signature): (&str, String, &str) =
-            if date.is_none() || domain.is_none() || signature.is_none() {
+        let (date_str, domain, signature): (&str, String, &str) = match (date, domain, signature) {
+            (Some(date), Some(domain), Some(signature)) => (date, domain, signature),
+            _ => {
                 log::warn!("{ip}: Missing headers");
-                return request::Outcome::Error((http::Status::PreconditionFailed, ()));
-            } else {
-                (date.unwrap(), domain.unwrap(), signature.unwrap())
-            };
+                return request::Outcome::Error((
+                    http::Status::PreconditionFailed,
+                    AuthError::Invalid,
+                ));
+            }
+        };
+
+        if nonce.is_some_and(|nonce| {
+            nonce.is_empty()
+                || nonce.len() > MAX_NONCE_LENGTH
+                || !nonce.bytes().all(|b| b.is_ascii_graphic() && b != b';')
+        }) {
+            log::warn!("Invalid nonce from {ip} for {domain}");
+            return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+        }
 
-        if let Ok(dt) =
+        // Empty when absent, a semi-column would let a parameter pass for the next one
+        let mut params: Vec<&str> = Vec::with_capacity(SIGNED_PARAMS.len());
+        for name in SIGNED_PARAMS {
+            let value = request
+                .query_value::<&str>(name)
+                .and_then(Result::ok)
+                .unwrap_or("");
+            if value.contains(';') {
+                log::warn!("Invalid {name} parameter from {ip} for {domain}");
+                return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+            }
+            params.push(value);
+        }
+
+        // The version 2 signatures include the addresses, from the Ftddns-Ip header or the client IP
+        let signed_addresses: Option<(String, Addresses)> = match (version.trim(), signed_ip) {
+            ("1", None) => None,
+            ("2", header) => match v2_addresses(header, ip) {
+                Ok(signed) => Some(signed),
+                Err(()) => {
+                    log::warn!("Invalid Ftddns-Ip header from {ip} for {domain}");
+                    return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+                }
+            },
+            _ => {
+                log::warn!("Unsupported signature version from {ip} for {domain}");
+                return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+            }
+        };
+
+        let expires_at = if let Ok(dt) =
             chrono::DateTime::parse_from_rfc3339(date_str).map(|d| d.with_timezone(&Utc))
         {
-            const SIGNATURE_TIME_MARGIN: i64 = 60;
             if dt > Utc::now() + chrono::Duration::seconds(SIGNATURE_TIME_MARGIN) {
                 log::warn!("{ip}'s signature date is in the future for {domain}");
-                return request::Outcome::Error((http::Status::NotAcceptable, ()));
+                return request::Outcome::Error((http::Status::NotAcceptable, AuthError::Invalid));
             }
 
             if dt < Utc::now() - chrono::Duration::seconds(SIGNATURE_TIME_MARGIN) {
                 log::warn!("{ip}'s signature date is in the past for {domain}");
-                return request::Outcome::Error((http::Status::NotAcceptable, ()));
+                return request::Outcome::Error((http::Status::NotAcceptable, AuthError::Invalid));
             }
+
+            dt + chrono::Duration::seconds(SIGNATURE_TIME_MARGIN)
         } else {
             log::warn!("Invalid date format from {ip} for {domain}");
-            return request::Outcome::Error((http::Status::BadRequest, ()));
-        }
+            return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+        };
 
-        let public_key: PublicKey = if let Ok(result) = Self::find_key_by_domain(&domain, db).await
-        {
-            if let Some(public_key) = result {
-                public_key
+        let (public_keys, account): (Vec<PublicKey>, Model) =
+            if let Ok(result) = Self::find_keys_by_domain(&domain, db).await {
+                if let Some(found) = result {
+                    found
+                } else {
+                    log::warn!("Domain requested by {ip} does not exist in the system: {domain}");
+                    return request::Outcome::Error((http::Status::NotFound, AuthError::Invalid));
+                }
             } else {
-                log::warn!("Domain requested by {ip} does not exist in the system: {domain}");
-                return request::Outcome::Error((http::Status::NotFound, ()));
-            }
-        } else {
-            log::error!("Database error while serving {ip}");
-            return request::Outcome::Error((http::Status::InternalServerError, ()));
-        };
+                log::error!("Database error while serving {ip}");
+                return request::Outcome::Error((
+                    http::Status::InternalServerError,
+                    AuthError::Invalid,
+                ));
+            };
 
-        let binary_signature = if let Ok(binary) = BASE64_STANDARD.decode(signature.as_bytes()) {
-            binary
-        } else {
-            log::warn!("Invalid base64 encoding sent by {ip} while requesting {domain}");
-            return request::Outcome::Error((http::Status::BadRequest, ()));
-        };
+        let binary_signature =
+            if let Ok(binary) = BASE64_STANDARD.decode(openssh::unarmor(signature)) {
+                binary
+            } else {
+                log::warn!("Invalid base64 encoding sent by {ip} while requesting {domain}");
+                return request::Outcome::Error((http::Status::BadRequest, AuthError::Invalid));
+            };
 
-        let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key).unwrap();
+        if signed_addresses.is_none() && !account.legacy_signatures.unwrap_or(true) {
+            log::warn!("{ip} sent a signature without the IP for {domain}");
+            record_rejection(request, &domain, AuthMethod::Signing, "Legacy signature").await;
+            return request::Outcome::Error((http::Status::Unauthorized, AuthError::Invalid));
+        }
 
-        let verification =
-            verifier.verify_oneshot(&binary_signature, format!("{date_str};{domain}").as_bytes());
+        // The route is signed so a signature made for one route can't be sent to another
+        let route = format!("{} {}", request.method(), request.uri().path());
+        let v2 = signed_addresses.as_ref().map(|(list, _)| V2Fields {
+            addresses: list,
+            route: &route,
+            params: &params,
+        });
+        let message = signed_message(date_str, &domain, v2, nonce);
+        let verified = public_keys.iter().any(|public_key| {
+            signing_key::verify(public_key, message.as_bytes(), &binary_signature)
+        });
 
-        match verification {
-            Err(_) => {
-                log::warn!("Signature verification error for {domain} from {ip}");
-                return request::Outcome::Error((http::Status::Unauthorized, ()));
-            }
-            Ok(v) => {
-                if !v {
-                    log::warn!("Signature verification failed for {domain} from {ip}");
-                    return request::Outcome::Error((http::Status::Unauthorized, ()));
-                }
-            }
+        if !verified {
+            log::warn!("Signature verification failed for {domain} from {ip}");
+            record_rejection(request, &domain, AuthMethod::Signing, "Invalid signature").await;
+            return request::Outcome::Error((http::Status::Unauthorized, AuthError::Invalid));
+        }
+
+        // Keyed on the signed message rather than the signature, as some schemes yield several
+        // valid signatures of the same message
+        let replays: &ReplayCache = request.rocket().state::<ReplayCache>().unwrap();
+        if !replays.accept(&message, expires_at) {
+            log::warn!("{ip} replayed a signature for {domain}");
+            record_rejection(request, &domain, AuthMethod::Signing, "Replayed signature").await;
+            return request::Outcome::Error((http::Status::Conflict, AuthError::Invalid));
+        }
+
+        if account.disabled.unwrap_or(false) {
+            log::warn!("{ip}: The disabled account {domain} attempted an update");
+            record_rejection(request, &domain, AuthMethod::Signing, "Account disabled").await;
+            return request::Outcome::Error((http::Status::Forbidden, AuthError::Disabled));
+        }
+
+        request::Outcome::Success(Self(
+            domain,
+            signed_addresses.map(|(_, addresses)| addresses),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATE: &str = "2024-05-01 12:00:00+00:00";
+    const DOMAIN: &str = "a.dyn.example.com";
+
+    #[test]
+    fn signs_the_ip_header_as_sent() {
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let (list, addresses) = v2_addresses(Some("203.0.113.7, 2001:db8::1"), client).unwrap();
+
+        assert_eq!(list, "203.0.113.7, 2001:db8::1");
+        assert_eq!(
+            addresses,
+            Addresses::from_list("203.0.113.7,2001:db8::1").unwrap()
+        );
+        assert_eq!(
+            v2_addresses(Some("203.0.113.7,198.51.100.1"), client),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn signs_the_client_ip_without_the_header() {
+        let client: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(
+            v2_addresses(None, client),
+            Ok((String::from("2001:db8::1"), Addresses::from(client)))
+        );
+
+        // IPv4 clients reaching a dual-stack socket are signed as IPv4
+        let mapped: IpAddr = "::ffff:192.0.2.1".parse().unwrap();
+        let (list, addresses) = v2_addresses(None, mapped).unwrap();
+        assert_eq!(list, "192.0.2.1");
+        assert_eq!(addresses.ipv4, Some("192.0.2.1".parse().unwrap()));
+        assert_eq!(addresses.ipv6, None);
+    }
+
+    #[test]
+    fn builds_version_1_messages() {
+        assert_eq!(
+            signed_message(DATE, DOMAIN, None, None),
+            "2024-05-01 12:00:00+00:00;a.dyn.example.com"
+        );
+        assert_eq!(
+            signed_message(DATE, DOMAIN, None, Some("n0nce")),
+            "2024-05-01 12:00:00+00:00;a.dyn.example.com;n0nce"
+        );
+    }
+
+    #[test]
+    fn builds_version_2_messages() {
+        let (list, _) = v2_addresses(None, "192.0.2.1".parse().unwrap()).unwrap();
+        let v2 = V2Fields {
+            addresses: &list,
+            route: "GET /unsecure/nic/update",
+            params: &["", ""],
         };
+        assert_eq!(
+            signed_message(DATE, DOMAIN, Some(v2), None),
+            "v2;2024-05-01 12:00:00+00:00;a.dyn.example.com;192.0.2.1;GET /unsecure/nic/update;;"
+        );
 
-        request::Outcome::Success(Self(domain))
+        let v2 = V2Fields {
+            addresses: "203.0.113.7,2001:db8::1",
+            route: "POST /unsecure/acme/challenge",
+            params: &["b.dyn.example.com", "value"],
+        };
+        assert_eq!(
+            signed_message(DATE, DOMAIN, Some(v2), Some("n0nce")),
+            "v2;2024-05-01 12:00:00+00:00;a.dyn.example.com;203.0.113.7,2001:db8::1;\
+             POST /unsecure/acme/challenge;b.dyn.example.com;value;n0nce"
+        );
     }
 }
@@ -0,0 +1,418 @@
+/// The public keys of the signing accounts
+///
+/// An account can hold many active keys at once, so a fleet can switch keys without downtime.
+use std::env;
+
+use chrono::{DateTime, Utc};
+use openssl::{
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{Id, PKeyRef, Public},
+    rsa::Padding,
+    sha::sha256,
+    sign::{RsaPssSaltlen, Verifier},
+};
+use rocket::{fairing::AdHoc, serde::Serialize};
+use sea_orm::{entity::prelude::*, QueryOrder, Set};
+
+use super::{openssh, PublicKey};
+
+/// Default lowest size of the RSA keys accepted at upload, in bits
+const DEFAULT_RSA_MIN_BITS: u32 = 2048;
+
+/// The algorithms of the public keys accepted for the signing accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "kebab-case")]
+pub enum KeyType {
+    /// RSA key, verifying PKCS #1 v1.5 and PSS signatures
+    Rsa,
+    /// RSA key restricted to PSS signatures
+    RsaPss,
+    /// ECDSA key on the P-256, P-384 or P-521 curve
+    Ecdsa,
+    Ed25519,
+}
+
+impl KeyType {
+    /// The type of the key, `None` when it isn't supported
+    pub fn of(key: &PKeyRef<Public>) -> Option<Self> {
+        match key.id() {
+            Id::RSA => Some(Self::Rsa),
+            Id::RSA_PSS => Some(Self::RsaPss),
+            Id::EC => Some(Self::Ecdsa),
+            Id::ED25519 => Some(Self::Ed25519),
+            _ => None,
+        }
+    }
+}
+
+/// The strength required from the keys uploaded, managed in Rocket's state
+///
+/// The keys stored before the policy changed keep verifying signatures.
+pub struct KeyPolicy {
+    rsa_min_bits: u32,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self {
+            rsa_min_bits: DEFAULT_RSA_MIN_BITS,
+        }
+    }
+}
+
+impl KeyPolicy {
+    /// Accepts the key when its type is supported and it is strong enough
+    pub fn check(&self, key: &PKeyRef<Public>) -> Result<KeyType, String> {
+        let key_type = KeyType::of(key)
+            .ok_or_else(|| String::from("Only RSA, ECDSA and Ed25519 public keys are accepted."))?;
+
+        match key_type {
+            KeyType::Rsa | KeyType::RsaPss if key.bits() < self.rsa_min_bits => Err(format!(
+                "RSA public keys must have at least {} bits.",
+                self.rsa_min_bits
+            )),
+            KeyType::Ecdsa
+                if !key.ec_key().is_ok_and(|ec| {
+                    matches!(
+                        ec.group().curve_name(),
+                        Some(Nid::X9_62_PRIME256V1 | Nid::SECP384R1 | Nid::SECP521R1)
+                    )
+                }) =>
+            {
+                Err(String::from(
+                    "ECDSA public keys must use the P-256, P-384 or P-521 curve.",
+                ))
+            }
+            _ => Ok(key_type),
+        }
+    }
+}
+
+/// Loads the key policy from `SIGNING_RSA_MIN_BITS`
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Signing key policy", |rocket| {
+        Box::pin(async {
+            let policy = env::var("SIGNING_RSA_MIN_BITS")
+                .ok()
+                .map(|v| KeyPolicy {
+                    rsa_min_bits: v
+                        .parse()
+                        .expect("SIGNING_RSA_MIN_BITS must be a number of bits."),
+                })
+                .unwrap_or_default();
+
+            rocket.manage(policy)
+        })
+    })
+}
+
+/// Whether the signature of the message is valid for the key
+///
+/// The RSA and ECDSA signatures use a SHA-256 digest, Ed25519 signs the message itself.
+/// The SSHSIG signatures of `ssh-keygen -Y sign` carry their own algorithm.
+pub fn verify(key: &PKeyRef<Public>, message: &[u8], signature: &[u8]) -> bool {
+    if openssh::is_sshsig(signature) {
+        return openssh::verify_sshsig(key, message, signature).unwrap_or_else(|| {
+            log::debug!("Malformed SSHSIG signature");
+            false
+        });
+    }
+
+    let verify_with = |pss: bool| {
+        verify_padded(key, pss, message, signature).unwrap_or_else(|e| {
+            log::debug!("Signature verification error: {}", e);
+            false
+        })
+    };
+
+    match KeyType::of(key) {
+        Some(KeyType::Rsa) => verify_with(false) || verify_with(true),
+        Some(KeyType::RsaPss) => verify_with(true),
+        Some(KeyType::Ecdsa | KeyType::Ed25519) => verify_with(false),
+        None => false,
+    }
+}
+
+fn verify_padded(
+    key: &PKeyRef<Public>,
+    pss: bool,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, openssl::error::ErrorStack> {
+    let mut verifier = if key.id() == Id::ED25519 {
+        Verifier::new_without_digest(key)?
+    } else {
+        Verifier::new(MessageDigest::sha256(), key)?
+    };
+
+    if pss {
+        verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+        // The maximum length stands for any salt length when verifying
+        verifier.set_rsa_pss_saltlen(RsaPssSaltlen::MAXIMUM_LENGTH)?;
+    }
+
+    verifier.verify_oneshot(signature, message)
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "signing_key")]
+#[serde(crate = "rocket::serde")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// The domain of the signing account owning the key
+    pub domain: String,
+    /// The hexadecimal SHA-256 digest of the DER encoded public key
+    pub fingerprint: String,
+    /// A free text to tell the keys apart
+    pub label: Option<String>,
+    /// The PEM encoded public key
+    pub public_key: String,
+    pub created_at: DateTimeUtc,
+    /// The key is refused for signatures after this time
+    pub not_after: Option<DateTimeUtc>,
+    /// When the key was revoked by an admin
+    pub revoked_at: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Whether the key can be used to verify a signature at the given time
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.not_after.is_none_or(|not_after| now < not_after)
+    }
+
+    pub fn public_key(&self) -> Option<PublicKey> {
+        PublicKey::public_key_from_pem(self.public_key.as_bytes()).ok()
+    }
+}
+
+/// A key as listed to the admins
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SigningKeyView {
+    #[serde(flatten)]
+    pub key: Model,
+    pub active: bool,
+    /// The algorithm of the key, `None` when it isn't supported anymore
+    pub key_type: Option<KeyType>,
+}
+
+impl From<Model> for SigningKeyView {
+    fn from(key: Model) -> Self {
+        Self {
+            active: key.is_active(Utc::now()),
+            key_type: key.public_key().as_deref().and_then(KeyType::of),
+            key,
+        }
+    }
+}
+
+/// The hexadecimal SHA-256 digest of the DER encoded public key
+pub fn fingerprint(pub_key: &PKeyRef<Public>) -> String {
+    sha256(&pub_key.public_key_to_der().unwrap())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+pub async fn add(
+    domain: &str,
+    pub_key: &PKeyRef<Public>,
+    label: Option<String>,
+    not_after: Option<DateTime<Utc>>,
+    db: &impl ConnectionTrait,
+) -> Result<Model, DbErr> {
+    ActiveModel {
+        domain: Set(domain.to_owned()),
+        fingerprint: Set(fingerprint(pub_key)),
+        label: Set(label),
+        public_key: Set(String::from_utf8(pub_key.public_key_to_pem().unwrap()).unwrap()),
+        created_at: Set(Utc::now()),
+        not_after: Set(not_after),
+        revoked_at: Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+/// Every key of the domain, revoked and expired ones included, oldest first
+pub async fn find_by_domain(domain: &str, db: &DbConn) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::Domain.eq(domain))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await
+}
+
+/// The keys of the domain that can currently verify a signature
+pub async fn find_active(domain: &str, db: &DbConn) -> Result<Vec<Model>, DbErr> {
+    let now = Utc::now();
+    Ok(Entity::find()
+        .filter(Column::Domain.eq(domain))
+        .filter(Column::RevokedAt.is_null())
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|k| k.is_active(now))
+        .collect())
+}
+
+/// Revokes a key of the domain, returning false when no active key has this ID
+pub async fn revoke(domain: &str, id: i64, db: &DbConn) -> Result<bool, DbErr> {
+    Entity::update_many()
+        .col_expr(Column::RevokedAt, Expr::value(Utc::now()))
+        .filter(Column::Domain.eq(domain))
+        .filter(Column::Id.eq(id))
+        .filter(Column::RevokedAt.is_null())
+        .exec(db)
+        .await
+        .map(|r| r.rows_affected > 0)
+}
+
+/// Revokes every key of the domain
+pub async fn revoke_all(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(Column::RevokedAt, Expr::value(Utc::now()))
+        .filter(Column::Domain.eq(domain))
+        .filter(Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_by_domain(domain: &str, db: &impl ConnectionTrait) -> Result<(), DbErr> {
+    Entity::delete_many()
+        .filter(Column::Domain.eq(domain))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        pkey::{PKey, Private},
+        pkey_ctx::PkeyCtx,
+        rsa::Rsa,
+        sign::Signer,
+    };
+
+    use super::*;
+
+    /// The message of a version 1 signature, `{date};{domain}`
+    const MESSAGE: &[u8] = b"2026-10-17 10:00:00+00:00;device.dyn.example.com";
+
+    fn public(private: &PKey<Private>) -> PublicKey {
+        PublicKey::public_key_from_der(&private.public_key_to_der().unwrap()).unwrap()
+    }
+
+    fn rsa_key() -> PKey<Private> {
+        PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+    }
+
+    fn ec_key(nid: Nid) -> PKey<Private> {
+        let group = EcGroup::from_curve_name(nid).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// Signs the message with SHA-256, using PSS padding with the salt length when given
+    fn sign(private: &PKey<Private>, pss: Option<RsaPssSaltlen>) -> Vec<u8> {
+        let mut signer = Signer::new(MessageDigest::sha256(), private).unwrap();
+        if let Some(salt) = pss {
+            signer.set_rsa_padding(Padding::PKCS1_PSS).unwrap();
+            signer.set_rsa_pss_saltlen(salt).unwrap();
+        }
+
+        signer.sign_oneshot_to_vec(MESSAGE).unwrap()
+    }
+
+    #[test]
+    fn verifies_rsa_pkcs1_signatures() {
+        let private = rsa_key();
+        let key = public(&private);
+
+        assert_eq!(KeyType::of(&key), Some(KeyType::Rsa));
+        assert!(verify(&key, MESSAGE, &sign(&private, None)));
+        assert!(!verify(
+            &key,
+            b"2026-10-17 10:00:00+00:00;other.dyn.example.com",
+            &sign(&private, None)
+        ));
+    }
+
+    #[test]
+    fn verifies_rsa_pss_signatures_with_any_salt_length() {
+        let private = rsa_key();
+        let key = public(&private);
+
+        let digest_length = sign(&private, Some(RsaPssSaltlen::DIGEST_LENGTH));
+        let max_length = sign(&private, Some(RsaPssSaltlen::MAXIMUM_LENGTH));
+
+        assert!(verify(&key, MESSAGE, &digest_length));
+        assert!(verify(&key, MESSAGE, &max_length));
+    }
+
+    #[test]
+    fn verifies_rsa_pss_keys() {
+        let mut ctx = PkeyCtx::new_id(Id::RSA_PSS).unwrap();
+        ctx.keygen_init().unwrap();
+        let private = ctx.keygen().unwrap();
+        let key = public(&private);
+
+        assert_eq!(KeyType::of(&key), Some(KeyType::RsaPss));
+        assert!(verify(
+            &key,
+            MESSAGE,
+            &sign(&private, Some(RsaPssSaltlen::DIGEST_LENGTH))
+        ));
+        assert!(verify(
+            &key,
+            MESSAGE,
+            &sign(&private, Some(RsaPssSaltlen::MAXIMUM_LENGTH))
+        ));
+    }
+
+    #[test]
+    fn verifies_ecdsa_signatures_on_every_curve() {
+        for nid in [Nid::X9_62_PRIME256V1, Nid::SECP384R1, Nid::SECP521R1] {
+            let private = ec_key(nid);
+            let key = public(&private);
+
+            assert_eq!(KeyPolicy::default().check(&key), Ok(KeyType::Ecdsa));
+            assert!(verify(&key, MESSAGE, &sign(&private, None)), "{nid:?}");
+        }
+    }
+
+    #[test]
+    fn verifies_ed25519_signatures() {
+        let private = PKey::generate_ed25519().unwrap();
+        let key = public(&private);
+        let signature = Signer::new_without_digest(&private)
+            .unwrap()
+            .sign_oneshot_to_vec(MESSAGE)
+            .unwrap();
+
+        assert_eq!(KeyType::of(&key), Some(KeyType::Ed25519));
+        assert!(verify(&key, MESSAGE, &signature));
+        assert!(!verify(&key, MESSAGE, &signature[1..]));
+    }
+
+    #[test]
+    fn refuses_signatures_of_another_key() {
+        let key = public(&ec_key(Nid::X9_62_PRIME256V1));
+        let signature = sign(&ec_key(Nid::X9_62_PRIME256V1), None);
+
+        assert!(!verify(&key, MESSAGE, &signature));
+    }
+}
@@ -0,0 +1,312 @@
+/// TXT records for the ACME DNS-01 challenges of the accounts
+///
+/// The values are kept in the database so the whole TXT record set of a name is written at once,
+/// as validating a wildcard certificate along with its apex needs two values on the same name.
+use std::str::FromStr;
+
+use chrono::Utc;
+use fqdn::FQDN;
+use rocket::{
+    http::Status,
+    response::{self, Responder},
+    serde::{
+        json::{json, Json},
+        Deserialize,
+    },
+    tokio::sync::Mutex,
+    Request, Response,
+};
+use sea_orm::{entity::prelude::*, QueryOrder, Set};
+
+use crate::{
+    account::{self, HostnamePattern},
+    client_response::ClientResponse,
+    dns::Dns,
+    ttl::TtlPolicy,
+};
+
+/// Prefix of the names holding the challenges of a hostname
+const CHALLENGE_PREFIX: &str = "_acme-challenge.";
+
+/// Number of values kept for a name, the oldest one being dropped when a new one is set
+///
+/// Two values cover a wildcard certificate validated along with its apex, like acme-dns does.
+const MAX_VALUES: usize = 2;
+
+/// Serializes the changes, so concurrent challenges of a name don't drop each other's values
+static CHANGE_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "acme_challenge")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// The `_acme-challenge` name holding the value
+    pub name: String,
+    pub value: String,
+    /// The domain of the account that set the value
+    pub domain: String,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+fn db_error(e: DbErr) -> ClientResponse {
+    log::error!("An error occured communicating with the database: {}", e);
+    ClientResponse::InternalServerError(String::new())
+}
+
+/// Whether the value has the form of a DNS-01 key authorization digest, 43 base64url characters
+pub fn is_valid_value(value: &str) -> bool {
+    value.len() == 43
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// The hostname whose challenge the account sets, the domain of the account by default
+///
+/// The hostname can be given with or without its `_acme-challenge` label.
+pub async fn owned_hostname(
+    domain: &str,
+    hostname: Option<&str>,
+    db: &DbConn,
+) -> Result<FQDN, ClientResponse> {
+    let hostname = match hostname.map(str::trim).filter(|h| !h.is_empty()) {
+        Some(hostname) => {
+            FQDN::from_str(hostname.strip_prefix(CHALLENGE_PREFIX).unwrap_or(hostname)).map_err(
+                |_| ClientResponse::BadRequest(String::from("The hostname provided is invalid.")),
+            )?
+        }
+        None => match HostnamePattern::from_str(domain) {
+            Ok(HostnamePattern::Exact(hostname)) => hostname,
+            _ => {
+                return Err(ClientResponse::BadRequest(String::from(
+                    "The hostname parameter is required for a subtree account.",
+                )))
+            }
+        },
+    };
+
+    let owned = account::hostnames(domain, db).await.map_err(db_error)?;
+    if !owned.iter().any(|pattern| pattern.matches(&hostname)) {
+        log::warn!("The account {domain} attempted to set a challenge for {hostname}");
+        return Err(ClientResponse::NotAcceptable(String::from(
+            "The hostname requested is not owned by the account.",
+        )));
+    }
+
+    Ok(hostname)
+}
+
+/// The hostname of an `_acme-challenge` name, `None` for any other name
+pub fn challenge_hostname(name: &str) -> Option<&str> {
+    name.trim()
+        .trim_end_matches('.')
+        .strip_prefix(CHALLENGE_PREFIX)
+}
+
+fn challenge_name(hostname: &FQDN) -> Result<FQDN, ClientResponse> {
+    FQDN::from_str(&format!("{CHALLENGE_PREFIX}{hostname}")).map_err(|_| {
+        ClientResponse::BadRequest(String::from(
+            "The hostname provided is too long for a challenge.",
+        ))
+    })
+}
+
+/// The values set for the name, newest first
+async fn find_by_name(name: &FQDN, db: &DbConn) -> Result<Vec<Model>, ClientResponse> {
+    Entity::find()
+        .filter(Column::Name.eq(name.to_string()))
+        .order_by_desc(Column::Id)
+        .all(db)
+        .await
+        .map_err(db_error)
+}
+
+async fn delete_ids(ids: Vec<i64>, db: &DbConn) -> Result<(), ClientResponse> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    Entity::delete_many()
+        .filter(Column::Id.is_in(ids))
+        .exec(db)
+        .await
+        .map_err(db_error)?;
+
+    Ok(())
+}
+
+/// Adds the value to the challenge of the hostname, dropping the oldest values over the limit
+///
+/// The challenge records use the lowest TTL allowed, so a retried validation doesn't get a cached answer.
+pub async fn present(
+    domain: &str,
+    hostname: &FQDN,
+    value: &str,
+    dns: &Dns,
+    ttls: &TtlPolicy,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    if !is_valid_value(value) {
+        return Err(ClientResponse::BadRequest(String::from(
+            "The challenge value must be 43 base64url characters.",
+        )));
+    }
+
+    let name = challenge_name(hostname)?;
+    let _lock = CHANGE_LOCK.lock().await;
+
+    let existing = find_by_name(&name, db).await?;
+    let is_new = !existing.iter().any(|model| model.value == value);
+    let mut kept: Vec<&Model> = existing.iter().filter(|m| m.value != value).collect();
+    kept.truncate(MAX_VALUES - 1);
+
+    let values: Vec<String> = std::iter::once(value.to_owned())
+        .chain(kept.iter().map(|model| model.value.clone()))
+        .collect();
+    dns.set_txt_records(&name, &values, ttls.min()).await?;
+
+    let dropped: Vec<i64> = existing
+        .iter()
+        .filter(|model| model.value != value && !kept.contains(model))
+        .map(|model| model.id)
+        .collect();
+    delete_ids(dropped, db).await?;
+
+    if is_new {
+        ActiveModel {
+            name: Set(name.to_string()),
+            value: Set(value.to_owned()),
+            domain: Set(domain.to_owned()),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(db_error)?;
+    }
+
+    log::info!("The account {domain} set a challenge for {hostname}");
+    Ok(())
+}
+
+/// Removes the value from the challenge of the hostname, or every value without one
+pub async fn cleanup(
+    domain: &str,
+    hostname: &FQDN,
+    value: Option<&str>,
+    dns: &Dns,
+    ttls: &TtlPolicy,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    let name = challenge_name(hostname)?;
+    let _lock = CHANGE_LOCK.lock().await;
+
+    let existing = find_by_name(&name, db).await?;
+    let (removed, kept): (Vec<Model>, Vec<Model>) = existing
+        .into_iter()
+        .partition(|model| value.is_none_or(|value| model.value == value));
+    if removed.is_empty() {
+        return Ok(());
+    }
+
+    let values: Vec<String> = kept.into_iter().map(|model| model.value).collect();
+    dns.set_txt_records(&name, &values, ttls.min()).await?;
+    delete_ids(removed.into_iter().map(|model| model.id).collect(), db).await?;
+
+    log::info!("The account {domain} cleared a challenge for {hostname}");
+    Ok(())
+}
+
+/// Deletes the challenge records set by the account of the domain
+pub async fn clear_account(
+    domain: &str,
+    dns: &Dns,
+    ttls: &TtlPolicy,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    let _lock = CHANGE_LOCK.lock().await;
+
+    let models = Entity::find()
+        .filter(Column::Domain.eq(domain))
+        .all(db)
+        .await
+        .map_err(db_error)?;
+
+    let mut names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    for name in names {
+        if let Ok(name) = FQDN::from_str(name) {
+            dns.set_txt_records(&name, &[], ttls.min()).await?;
+        }
+
+        Entity::delete_many()
+            .filter(Column::Name.eq(name))
+            .exec(db)
+            .await
+            .map_err(db_error)?;
+    }
+
+    Ok(())
+}
+
+/// The body of a lego `httpreq` present or cleanup request
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HttpReqChallenge {
+    /// The `_acme-challenge` name, with its trailing dot
+    pub fqdn: String,
+    pub value: String,
+}
+
+/// The body of an acme-dns update
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AcmeDnsUpdate {
+    /// The hostname whose challenge is set, the domain of the account when empty
+    #[serde(default)]
+    pub subdomain: String,
+    pub txt: String,
+}
+
+/// The JSON response of an acme-dns update
+pub enum AcmeDnsResponse {
+    Updated(String),
+    Error(Status, &'static str),
+}
+
+impl From<ClientResponse> for AcmeDnsResponse {
+    fn from(value: ClientResponse) -> Self {
+        match value {
+            ClientResponse::Ok(_) => Self::Updated(String::new()),
+            ClientResponse::BadRequest(_) => Self::Error(Status::BadRequest, "bad_subdomain"),
+            ClientResponse::NotAcceptable(_) | ClientResponse::Unauthorized(_) => {
+                Self::Error(Status::Unauthorized, "forbidden")
+            }
+            ClientResponse::ServiceUnavailable(_) => {
+                Self::Error(Status::ServiceUnavailable, "dns_error")
+            }
+            _ => Self::Error(Status::InternalServerError, "db_error"),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for AcmeDnsResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, body) = match self {
+            Self::Updated(txt) => (Status::Ok, json!({ "txt": txt })),
+            Self::Error(status, error) => (status, json!({ "error": error })),
+        };
+
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(unused)]
 use rocket::*;
 
-#[derive(Responder)]
+#[derive(Responder, Clone)]
 pub enum ClientResponse {
     #[response(status = 200)]
     Ok(String),
@@ -12,6 +12,9 @@ pub enum ClientResponse {
     #[response(status = 401)]
     Unauthorized(String),
 
+    #[response(status = 404)]
+    NotFound(String),
+
     #[response(status = 406)]
     NotAcceptable(String),
 
@@ -31,9 +34,6 @@ pub enum ClientResponse {
 impl ClientResponse {
     #[allow(unused)]
     pub fn is_ok(&self) -> bool {
-        match self {
-            ClientResponse::Ok(_) => true,
-            _ => false,
-        }
+        matches!(self, ClientResponse::Ok(_))
     }
 }
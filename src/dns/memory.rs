@@ -0,0 +1,109 @@
+use std::{collections::HashMap, env, str::FromStr, sync::RwLock};
+
+use fqdn::FQDN;
+
+use super::{join, ChangeResult, DnsBackend};
+use crate::{client_response::ClientResponse, ip::Addresses};
+
+/// DNS backend keeping the records in memory
+///
+/// Meant for staging and test setups, the records are lost when the program stops.
+pub struct MemoryBackend {
+    zones: Vec<FQDN>,
+    records: RwLock<HashMap<FQDN, Addresses>>,
+}
+
+impl MemoryBackend {
+    pub fn new(zones: Vec<FQDN>) -> Self {
+        Self {
+            zones,
+            records: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Builds the backend from the zones listed in `MEMORY_ZONE_LIST`, separated by `;`
+    pub fn from_env() -> Self {
+        let zones: Vec<FQDN> = env::var("MEMORY_ZONE_LIST")
+            .expect("MEMORY_ZONE_LIST environment variable is not set.")
+            .split(';')
+            .map(str::trim)
+            .filter(|z| !z.is_empty())
+            .map(|z| FQDN::from_str(z).expect("Invalid zone name in MEMORY_ZONE_LIST."))
+            .collect();
+
+        Self::new(zones)
+    }
+}
+
+#[rocket::async_trait]
+impl DnsBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "in-memory"
+    }
+
+    fn zones(&self) -> Vec<FQDN> {
+        self.zones.clone()
+    }
+
+    fn domain_included(&self, domain: &FQDN) -> bool {
+        self.zones.iter().any(|z| domain.is_subdomain_of(z))
+    }
+
+    async fn upsert_address_records(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult {
+        let mut records = self.records.write().unwrap();
+        for domain in domains {
+            let entry = records.entry(domain.clone()).or_default();
+            if addresses.ipv4.is_some() {
+                entry.ipv4 = addresses.ipv4;
+            }
+            if addresses.ipv6.is_some() {
+                entry.ipv6 = addresses.ipv6;
+            }
+        }
+
+        log::info!(
+            "Updated {} to IP {} with TTL {} in memory",
+            join(domains),
+            addresses,
+            ttl
+        );
+        Ok(None)
+    }
+
+    /// Only logs the change, as the TXT records can't be looked up
+    async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult {
+        if values.is_empty() {
+            log::info!("Deleted the TXT records of {} in memory", name);
+        } else {
+            log::info!(
+                "Set the TXT records of {} to {:?} with TTL {} in memory",
+                name,
+                values,
+                ttl
+            );
+        }
+        Ok(None)
+    }
+
+    async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult {
+        if self.records.write().unwrap().remove(domain).is_some() {
+            log::info!("Deleted the address records of {} in memory", domain);
+        }
+        Ok(None)
+    }
+
+    async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse> {
+        Ok(self
+            .records
+            .read()
+            .unwrap()
+            .get(domain)
+            .copied()
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,240 @@
+mod memory;
+#[cfg(feature = "powerdns")]
+mod powerdns;
+mod rfc2136;
+mod route53;
+
+use std::env;
+
+use fqdn::FQDN;
+use rocket::fairing::AdHoc;
+
+use crate::{client_response::ClientResponse, ip::Addresses};
+
+pub use memory::MemoryBackend;
+#[cfg(feature = "powerdns")]
+pub use powerdns::PowerDns;
+pub use rfc2136::Rfc2136;
+pub use route53::Route53;
+
+/// The result of a change submitted to a DNS backend, holding the change ID when one was returned
+pub type ChangeResult = Result<Option<String>, ClientResponse>;
+
+/// A DNS provider able to hold the records of the dynamic domains
+#[rocket::async_trait]
+pub trait DnsBackend: Send + Sync {
+    /// Name of the backend, used in the logs
+    fn name(&self) -> &'static str;
+
+    /// The zones enabled for dynamic DNS on this backend
+    fn zones(&self) -> Vec<FQDN>;
+
+    /// Whether the domain is inside one of the zones of this backend
+    fn domain_included(&self, domain: &FQDN) -> bool;
+
+    /// Upserts the A and/or AAAA records of the domains in a single change, with the TTL in seconds
+    ///
+    /// The domains are all inside the same zone of this backend.
+    async fn upsert_address_records(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult;
+
+    /// Replaces the TXT records of the name with the values, deleting them when there are none
+    async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult;
+
+    /// Deletes the A and AAAA records of the domain, if there are any
+    async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult;
+
+    /// Finds the addresses the domain currently points to
+    async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse>;
+}
+
+/// The index of a backend, along with one of its zones
+type BackendZone = (usize, FQDN);
+
+/// The DNS backends selected at startup, managed in Rocket's state
+///
+/// Each domain is served by the first backend holding a zone that includes it.
+pub struct Dns {
+    backends: Vec<Box<dyn DnsBackend>>,
+}
+
+impl Dns {
+    pub fn new(backends: Vec<Box<dyn DnsBackend>>) -> Self {
+        Self { backends }
+    }
+
+    fn backend_of(&self, domain: &FQDN) -> Result<&dyn DnsBackend, ClientResponse> {
+        self.backends
+            .iter()
+            .find(|b| b.domain_included(domain))
+            .map(Box::as_ref)
+            .ok_or_else(not_in_zone)
+    }
+
+    #[inline]
+    pub fn domain_included(&self, domain: &FQDN) -> bool {
+        self.backend_of(domain).is_ok()
+    }
+
+    pub fn zones(&self) -> Vec<FQDN> {
+        self.backends.iter().flat_map(|b| b.zones()).collect()
+    }
+
+    /// The index of the backend serving the domain, along with the zone including it
+    fn zone_of(&self, domain: &FQDN) -> Option<BackendZone> {
+        let index = self
+            .backends
+            .iter()
+            .position(|b| b.domain_included(domain))?;
+
+        self.backends[index]
+            .zones()
+            .into_iter()
+            .filter(|zone| domain.is_subdomain_of(zone))
+            .max_by_key(|zone| zone.as_bytes().len())
+            .map(|zone| (index, zone))
+    }
+
+    /// The zone including the domain, the most specific one when zones are nested
+    pub fn zone(&self, domain: &FQDN) -> Option<FQDN> {
+        self.zone_of(domain).map(|(_, zone)| zone)
+    }
+
+    pub async fn upsert_address_records(
+        &self,
+        domain: &FQDN,
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult {
+        if addresses.is_empty() {
+            return Err(ClientResponse::BadRequest(String::from(
+                "No IP address was provided for the update.",
+            )));
+        }
+
+        self.backend_of(domain)?
+            .upsert_address_records(std::slice::from_ref(domain), addresses, ttl)
+            .await
+    }
+
+    /// Upserts the records of the domains with one change per zone
+    ///
+    /// The TTL of the records of each change is given by the zone.
+    /// Returns the result of each change, along with the domains it holds.
+    pub async fn upsert_address_records_batch(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: impl Fn(&FQDN) -> u32,
+    ) -> Vec<(Vec<FQDN>, ChangeResult)> {
+        let mut groups: Vec<(Option<BackendZone>, Vec<FQDN>)> = Vec::new();
+        for domain in domains {
+            let zone = self.zone_of(domain);
+            match groups
+                .iter_mut()
+                .find(|(z, _)| zone.is_some() && z == &zone)
+            {
+                Some((_, group)) => group.push(domain.clone()),
+                None => groups.push((zone, vec![domain.clone()])),
+            }
+        }
+
+        let mut results = Vec::with_capacity(groups.len());
+        for (zone, group) in groups {
+            let result = match zone {
+                None => Err(not_in_zone()),
+                Some(_) if addresses.is_empty() => Err(ClientResponse::BadRequest(String::from(
+                    "No IP address was provided for the update.",
+                ))),
+                Some((index, zone)) => {
+                    self.backends[index]
+                        .upsert_address_records(&group, addresses, ttl(&zone))
+                        .await
+                }
+            };
+            results.push((group, result));
+        }
+
+        results
+    }
+
+    pub async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult {
+        self.backend_of(name)?
+            .set_txt_records(name, values, ttl)
+            .await
+    }
+
+    pub async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult {
+        self.backend_of(domain)?
+            .delete_address_records(domain)
+            .await
+    }
+
+    pub async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse> {
+        self.backend_of(domain)?
+            .lookup_address_records(domain)
+            .await
+    }
+}
+
+/// The domains separated by commas, for the logs
+fn join(domains: &[FQDN]) -> String {
+    domains
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn not_in_zone() -> ClientResponse {
+    ClientResponse::NotAcceptable(String::from(
+        "The domain requested is not in any hosted zone that is enabled for dynamic DNS.",
+    ))
+}
+
+/// Sets up the DNS backends listed in the `DNS_BACKEND` environment variable, separated by `;`
+///
+/// Defaults to Route 53.
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Setting up DNS backends", |rocket| {
+        Box::pin(async move {
+            let names = env::var("DNS_BACKEND").unwrap_or_else(|_| String::from("route53"));
+            let mut backends: Vec<Box<dyn DnsBackend>> = Vec::new();
+
+            for name in names.split(';').map(str::trim).filter(|n| !n.is_empty()) {
+                let backend: Box<dyn DnsBackend> = match name.to_lowercase().as_str() {
+                    "route53" => Box::new(Route53::connect().await),
+                    #[cfg(feature = "powerdns")]
+                    "powerdns" => Box::new(PowerDns::from_env()),
+                    #[cfg(not(feature = "powerdns"))]
+                    "powerdns" => panic!("The PowerDNS backend requires the powerdns feature."),
+                    "rfc2136" => Box::new(Rfc2136::from_env()),
+                    "memory" => Box::new(MemoryBackend::from_env()),
+                    other => panic!("Unknown DNS backend \"{other}\" in DNS_BACKEND."),
+                };
+
+                log::warn!(
+                    "Using the {} DNS backend for the zones {:?}",
+                    backend.name(),
+                    backend
+                        .zones()
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<String>>()
+                );
+
+                backends.push(backend);
+            }
+
+            if backends.is_empty() {
+                panic!("No DNS backend is set in DNS_BACKEND.");
+            }
+
+            rocket.manage(Dns::new(backends))
+        })
+    })
+}
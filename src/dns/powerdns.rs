@@ -0,0 +1,459 @@
+use std::{env, str::FromStr, sync::Arc, time::Duration};
+
+use fqdn::FQDN;
+use fqdn_trie::FqdnTrieMap;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{join, ChangeResult, DnsBackend};
+use crate::{client_response::ClientResponse, ip::Addresses};
+
+/// Maximum time allowed for a request to the PowerDNS API
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Server ID used by PowerDNS Authoritative in its API paths
+const DEFAULT_SERVER_ID: &str = "localhost";
+
+/// A zone hosted on a PowerDNS Authoritative server
+struct PowerDnsZone {
+    /// The URL of the zone in the API
+    url: String,
+    api_key: String,
+}
+
+/// DNS backend applying the changes through the PowerDNS Authoritative HTTP API
+pub struct PowerDns {
+    client: Client,
+    zone_map: FqdnTrieMap<FQDN, Option<Arc<PowerDnsZone>>>,
+    zones: Vec<FQDN>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RrSetPatch {
+    rrsets: Vec<RrSet>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RrSet {
+    name: String,
+    #[serde(rename = "type")]
+    rr_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u32>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    changetype: Option<&'static str>,
+    #[serde(default)]
+    records: Vec<RrSetRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RrSetRecord {
+    content: String,
+    #[serde(default)]
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Zone {
+    #[serde(default)]
+    rrsets: Vec<RrSet>,
+}
+
+impl RrSet {
+    fn replace(domain: &FQDN, rr_type: &str, contents: Vec<String>, ttl: u32) -> Self {
+        Self {
+            name: format!("{domain}."),
+            rr_type: rr_type.to_string(),
+            ttl: Some(ttl),
+            changetype: Some("REPLACE"),
+            records: contents
+                .into_iter()
+                .map(|content| RrSetRecord {
+                    content,
+                    disabled: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn delete(domain: &FQDN, rr_type: &str) -> Self {
+        Self {
+            name: format!("{domain}."),
+            rr_type: rr_type.to_string(),
+            ttl: None,
+            changetype: Some("DELETE"),
+            records: Vec::new(),
+        }
+    }
+
+    /// Replaces the TXT records of the name with the quoted values, deleting them when there are none
+    fn txt(name: &FQDN, values: &[String], ttl: u32) -> Self {
+        if values.is_empty() {
+            return Self::delete(name, "TXT");
+        }
+
+        Self::replace(
+            name,
+            "TXT",
+            values.iter().map(|value| format!("\"{value}\"")).collect(),
+            ttl,
+        )
+    }
+}
+
+/// The A and/or AAAA record sets replacing the ones of the domains
+fn address_rrsets(domains: &[FQDN], addresses: Addresses, ttl: u32) -> Vec<RrSet> {
+    let mut rrsets: Vec<RrSet> = Vec::with_capacity(2 * domains.len());
+
+    for domain in domains {
+        if let Some(ipv4) = addresses.ipv4 {
+            rrsets.push(RrSet::replace(domain, "A", vec![ipv4.to_string()], ttl));
+        }
+
+        if let Some(ipv6) = addresses.ipv6 {
+            rrsets.push(RrSet::replace(domain, "AAAA", vec![ipv6.to_string()], ttl));
+        }
+    }
+
+    rrsets
+}
+
+/// The first enabled A and AAAA records of the domain in the zone
+///
+/// Older PowerDNS versions ignore the rrset_name filter and return the whole zone.
+fn zone_addresses(zone: &Zone, domain: &FQDN) -> Addresses {
+    let mut addresses = Addresses::default();
+
+    for rrset in zone
+        .rrsets
+        .iter()
+        .filter(|rrset| FQDN::from_str(&rrset.name).is_ok_and(|n| &n == domain))
+    {
+        let value = rrset
+            .records
+            .iter()
+            .find(|record| !record.disabled)
+            .map(|record| record.content.as_str());
+
+        match rrset.rr_type.as_str() {
+            "A" => addresses.ipv4 = value.and_then(|v| v.parse().ok()),
+            "AAAA" => addresses.ipv6 = value.and_then(|v| v.parse().ok()),
+            _ => {}
+        }
+    }
+
+    addresses
+}
+
+impl PowerDns {
+    /// Builds the backend from the zones listed in `POWERDNS_ZONE_LIST`
+    ///
+    /// Zones are separated by `;`, and each zone is written as
+    /// `zone,API URL,API key[,server ID]`.
+    pub fn from_env() -> Self {
+        let zone_list = env::var("POWERDNS_ZONE_LIST")
+            .expect("POWERDNS_ZONE_LIST environment variable is not set.");
+
+        let mut zone_map: FqdnTrieMap<FQDN, Option<Arc<PowerDnsZone>>> = FqdnTrieMap::new(None);
+        let mut zones: Vec<FQDN> = Vec::new();
+
+        for entry in zone_list
+            .split(';')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let fields: Vec<&str> = entry.split(',').map(str::trim).collect();
+            let (zone, api_url, api_key, server_id) = match fields[..] {
+                [zone, api_url, api_key] => (zone, api_url, api_key, DEFAULT_SERVER_ID),
+                [zone, api_url, api_key, server_id] => (zone, api_url, api_key, server_id),
+                _ => panic!(
+                    "Invalid zone \"{entry}\" in POWERDNS_ZONE_LIST, expected 3 or 4 fields."
+                ),
+            };
+
+            let zone_fqdn = FQDN::from_str(zone).expect("Invalid zone name in POWERDNS_ZONE_LIST.");
+            let url = format!(
+                "{}/api/v1/servers/{server_id}/zones/{zone_fqdn}.",
+                api_url.trim_end_matches('/')
+            );
+
+            log::info!("Domain zone \"{zone_fqdn}\" available for services with the PowerDNS API at {api_url}");
+
+            zones.push(zone_fqdn.clone());
+            zone_map.insert(
+                zone_fqdn,
+                Some(Arc::new(PowerDnsZone {
+                    url,
+                    api_key: api_key.to_string(),
+                })),
+            );
+        }
+
+        zone_map.shrink_to_fit();
+
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Couldn't build the PowerDNS API client.");
+
+        Self {
+            client,
+            zone_map,
+            zones,
+        }
+    }
+
+    fn zone_of(&self, domain: &FQDN) -> Result<Arc<PowerDnsZone>, ClientResponse> {
+        self.zone_map
+            .lookup(domain)
+            .clone()
+            .ok_or_else(super::not_in_zone)
+    }
+
+    /// Sends the request with the API key of the zone, failing on any status other than a success
+    async fn send(
+        request: RequestBuilder,
+        zone: &PowerDnsZone,
+        failure: &str,
+    ) -> Result<reqwest::Response, ClientResponse> {
+        let unavailable = || ClientResponse::ServiceUnavailable(String::from(failure));
+
+        let response = request
+            .header("X-API-Key", &zone.api_key)
+            .send()
+            .await
+            .map_err(|e| {
+                log::warn!("PowerDNS API error with {}: {e}", zone.url);
+                unavailable()
+            })?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            status => {
+                log::warn!(
+                    "PowerDNS API error with {}: {status} {}",
+                    zone.url,
+                    response.text().await.unwrap_or_default()
+                );
+                Err(match status {
+                    StatusCode::UNPROCESSABLE_ENTITY => ClientResponse::BadRequest(String::from(
+                        "The DNS server refused the change.",
+                    )),
+                    _ => unavailable(),
+                })
+            }
+        }
+    }
+
+    async fn patch_rrsets(&self, domain: &FQDN, rrsets: Vec<RrSet>) -> Result<(), ClientResponse> {
+        let zone = self.zone_of(domain)?;
+
+        Self::send(
+            self.client.patch(&zone.url).json(&RrSetPatch { rrsets }),
+            &zone,
+            "Failed to submit domain change to PowerDNS.",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl DnsBackend for PowerDns {
+    fn name(&self) -> &'static str {
+        "PowerDNS"
+    }
+
+    fn zones(&self) -> Vec<FQDN> {
+        self.zones.clone()
+    }
+
+    #[inline]
+    fn domain_included(&self, domain: &FQDN) -> bool {
+        self.zone_map.lookup(domain).is_some()
+    }
+
+    /// Replaces the A and/or AAAA record sets of the domains in a single PATCH of the zone
+    async fn upsert_address_records(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult {
+        let Some(first) = domains.first() else {
+            return Ok(None);
+        };
+
+        self.patch_rrsets(first, address_rrsets(domains, addresses, ttl))
+            .await?;
+        log::info!(
+            "Updated {} to IP {} with TTL {}",
+            join(domains),
+            addresses,
+            ttl
+        );
+        Ok(None)
+    }
+
+    async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult {
+        self.patch_rrsets(name, vec![RrSet::txt(name, values, ttl)])
+            .await?;
+        log::info!("Set the TXT records of {} to {:?}", name, values);
+        Ok(None)
+    }
+
+    async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult {
+        self.patch_rrsets(
+            domain,
+            vec![RrSet::delete(domain, "A"), RrSet::delete(domain, "AAAA")],
+        )
+        .await?;
+        log::info!("Deleted the address records of {}", domain);
+        Ok(None)
+    }
+
+    async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse> {
+        let zone = self.zone_of(domain)?;
+        let name = format!("{domain}.");
+
+        let response = Self::send(
+            self.client
+                .get(&zone.url)
+                .query(&[("rrsets", "true"), ("rrset_name", name.as_str())]),
+            &zone,
+            "Failed to fetch the records from PowerDNS.",
+        )
+        .await?;
+
+        let zone_content: Zone = response.json().await.map_err(|e| {
+            log::warn!(
+                "Invalid zone returned by the PowerDNS API at {}: {e}",
+                zone.url
+            );
+            ClientResponse::ServiceUnavailable(String::from(
+                "Failed to fetch the records from PowerDNS.",
+            ))
+        })?;
+
+        Ok(zone_addresses(&zone_content, domain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use rocket::serde::json::{self, json, Value};
+
+    use super::*;
+
+    fn fqdn(name: &str) -> FQDN {
+        FQDN::from_str(name).unwrap()
+    }
+
+    fn patch(rrsets: Vec<RrSet>) -> Value {
+        json::from_str(&json::to_string(&RrSetPatch { rrsets }).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn replaces_address_record_sets() {
+        let addresses = Addresses {
+            ipv4: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            ipv6: Some("2001:db8::1".parse::<Ipv6Addr>().unwrap()),
+        };
+
+        assert_eq!(
+            patch(address_rrsets(
+                &[fqdn("host.dyn.example.com")],
+                addresses,
+                300
+            )),
+            json!({"rrsets": [
+                {
+                    "name": "host.dyn.example.com.",
+                    "type": "A",
+                    "ttl": 300,
+                    "changetype": "REPLACE",
+                    "records": [{"content": "192.0.2.1", "disabled": false}],
+                },
+                {
+                    "name": "host.dyn.example.com.",
+                    "type": "AAAA",
+                    "ttl": 300,
+                    "changetype": "REPLACE",
+                    "records": [{"content": "2001:db8::1", "disabled": false}],
+                },
+            ]})
+        );
+    }
+
+    #[test]
+    fn deletes_record_sets_without_ttl() {
+        let domain = fqdn("host.dyn.example.com");
+
+        assert_eq!(
+            patch(vec![
+                RrSet::delete(&domain, "A"),
+                RrSet::delete(&domain, "AAAA")
+            ]),
+            json!({"rrsets": [
+                {"name": "host.dyn.example.com.", "type": "A", "changetype": "DELETE", "records": []},
+                {"name": "host.dyn.example.com.", "type": "AAAA", "changetype": "DELETE", "records": []},
+            ]})
+        );
+    }
+
+    #[test]
+    fn quotes_txt_values() {
+        let name = fqdn("_acme-challenge.host.dyn.example.com");
+        let values = vec![String::from("token-1"), String::from("token-2")];
+
+        assert_eq!(
+            patch(vec![RrSet::txt(&name, &values, 60)]),
+            json!({"rrsets": [{
+                "name": "_acme-challenge.host.dyn.example.com.",
+                "type": "TXT",
+                "ttl": 60,
+                "changetype": "REPLACE",
+                "records": [
+                    {"content": "\"token-1\"", "disabled": false},
+                    {"content": "\"token-2\"", "disabled": false},
+                ],
+            }]})
+        );
+        assert_eq!(
+            patch(vec![RrSet::txt(&name, &[], 60)])["rrsets"][0]["changetype"],
+            "DELETE"
+        );
+    }
+
+    #[test]
+    fn finds_the_addresses_of_the_domain_in_a_whole_zone() {
+        let zone: Zone = json::from_str(
+            r#"{"name": "dyn.example.com.", "rrsets": [
+                {"name": "dyn.example.com.", "type": "A", "ttl": 300,
+                 "records": [{"content": "192.0.2.10", "disabled": false}]},
+                {"name": "other.dyn.example.com.", "type": "AAAA", "ttl": 300,
+                 "records": [{"content": "2001:db8::10", "disabled": false}]},
+                {"name": "host.dyn.example.com.", "type": "A", "ttl": 300,
+                 "records": [
+                    {"content": "192.0.2.1", "disabled": true},
+                    {"content": "192.0.2.2", "disabled": false}
+                 ]},
+                {"name": "host.dyn.example.com.", "type": "TXT", "ttl": 300,
+                 "records": [{"content": "\"192.0.2.3\"", "disabled": false}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let addresses = zone_addresses(&zone, &fqdn("host.dyn.example.com"));
+        assert_eq!(addresses.ipv4, Some(Ipv4Addr::new(192, 0, 2, 2)));
+        assert_eq!(addresses.ipv6, None);
+        assert!(zone_addresses(&zone, &fqdn("missing.dyn.example.com")).is_empty());
+    }
+}
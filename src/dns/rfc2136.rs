@@ -0,0 +1,492 @@
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::prelude::*;
+use fqdn::FQDN;
+use fqdn_trie::FqdnTrieMap;
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode, UpdateMessage},
+    rr::{
+        dnssec::{rdata::tsig::TsigAlgorithm, tsig::TSigner},
+        rdata::{A, AAAA, NULL, TXT},
+        DNSClass, Name, RData, Record, RecordType,
+    },
+};
+use rocket::tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use super::{join, ChangeResult, DnsBackend};
+use crate::{client_response::ClientResponse, ip::Addresses};
+
+/// Maximum time allowed for an exchange with the authoritative server
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Allowed clock difference between the program and the server for TSIG signatures
+const TSIG_FUDGE: u16 = 300;
+
+/// An authoritative server receiving the dynamic updates of a zone
+struct Rfc2136Zone {
+    zone: Name,
+    server: String,
+    signer: TSigner,
+}
+
+/// DNS backend sending RFC 2136 dynamic UPDATE messages signed with TSIG
+pub struct Rfc2136 {
+    zone_map: FqdnTrieMap<FQDN, Option<Arc<Rfc2136Zone>>>,
+    zones: Vec<FQDN>,
+}
+
+impl Rfc2136 {
+    /// Builds the backend from the zones listed in `RFC2136_ZONE_LIST`
+    ///
+    /// Zones are separated by `;`, and each zone is written as
+    /// `zone,server[:port],key name,algorithm,base64 secret`.
+    pub fn from_env() -> Self {
+        let zone_list = env::var("RFC2136_ZONE_LIST")
+            .expect("RFC2136_ZONE_LIST environment variable is not set.");
+
+        let mut zone_map: FqdnTrieMap<FQDN, Option<Arc<Rfc2136Zone>>> = FqdnTrieMap::new(None);
+        let mut zones: Vec<FQDN> = Vec::new();
+
+        for entry in zone_list
+            .split(';')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let fields: Vec<&str> = entry.split(',').map(str::trim).collect();
+            let [zone, server, key_name, algorithm, secret] = fields[..] else {
+                panic!("Invalid zone \"{entry}\" in RFC2136_ZONE_LIST, expected 5 fields.");
+            };
+
+            let zone_fqdn = FQDN::from_str(zone).expect("Invalid zone name in RFC2136_ZONE_LIST.");
+            let signer = TSigner::new(
+                BASE64_STANDARD
+                    .decode(secret)
+                    .expect("Invalid base64 TSIG secret in RFC2136_ZONE_LIST."),
+                TsigAlgorithm::from_name(
+                    Name::from_ascii(algorithm.to_lowercase().trim_end_matches('.'))
+                        .expect("Invalid TSIG algorithm in RFC2136_ZONE_LIST."),
+                ),
+                to_name(&FQDN::from_str(key_name).expect("Invalid TSIG key name.")),
+                TSIG_FUDGE,
+            )
+            .expect("Unsupported TSIG algorithm in RFC2136_ZONE_LIST, use hmac-sha256, hmac-sha384 or hmac-sha512.");
+
+            log::info!("Domain zone \"{zone}\" available for services with the server {server}");
+
+            zones.push(zone_fqdn.clone());
+            zone_map.insert(
+                zone_fqdn.clone(),
+                Some(Arc::new(Rfc2136Zone {
+                    zone: to_name(&zone_fqdn),
+                    server: with_default_port(server),
+                    signer,
+                })),
+            );
+        }
+
+        zone_map.shrink_to_fit();
+
+        Self { zone_map, zones }
+    }
+
+    fn zone_of(&self, domain: &FQDN) -> Result<Arc<Rfc2136Zone>, ClientResponse> {
+        self.zone_map
+            .lookup(domain)
+            .clone()
+            .ok_or_else(super::not_in_zone)
+    }
+
+    /// Sends the signed update for the domain, with the given records in the update section
+    async fn send_update(&self, domain: &FQDN, updates: Vec<Record>) -> Result<(), ClientResponse> {
+        let zone = self.zone_of(domain)?;
+        let response = exchange(&zone, update_message(&zone.zone, updates)).await?;
+
+        match response.response_code() {
+            ResponseCode::NoError => Ok(()),
+            code => {
+                log::warn!(
+                    "RFC 2136 update of {domain} refused by {}: {code}",
+                    zone.server
+                );
+                Err(ClientResponse::ServiceUnavailable(String::from(
+                    "The DNS server refused the update.",
+                )))
+            }
+        }
+    }
+
+    async fn query(
+        &self,
+        domain: &FQDN,
+        record_type: RecordType,
+    ) -> Result<Vec<Record>, ClientResponse> {
+        let zone = self.zone_of(domain)?;
+
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(false);
+        message.add_query(Query::query(to_name(domain), record_type));
+
+        let response = exchange(&zone, message).await?;
+
+        match response.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => Ok(response
+                .answers()
+                .iter()
+                .filter(|r| r.record_type() == record_type)
+                .cloned()
+                .collect()),
+            code => {
+                log::warn!("Query for {domain} refused by {}: {code}", zone.server);
+                Err(ClientResponse::ServiceUnavailable(String::from(
+                    "Failed to fetch the records from the DNS server.",
+                )))
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl DnsBackend for Rfc2136 {
+    fn name(&self) -> &'static str {
+        "RFC 2136"
+    }
+
+    fn zones(&self) -> Vec<FQDN> {
+        self.zones.clone()
+    }
+
+    #[inline]
+    fn domain_included(&self, domain: &FQDN) -> bool {
+        self.zone_map.lookup(domain).is_some()
+    }
+
+    /// Replaces the A and/or AAAA record sets of the domains in a single UPDATE message
+    async fn upsert_address_records(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult {
+        let Some(first) = domains.first() else {
+            return Ok(None);
+        };
+
+        self.send_update(first, address_updates(domains, addresses, ttl))
+            .await?;
+        log::info!(
+            "Updated {} to IP {} with TTL {}",
+            join(domains),
+            addresses,
+            ttl
+        );
+        Ok(None)
+    }
+
+    async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult {
+        let record_name = to_name(name);
+        let mut updates: Vec<Record> = vec![delete_rrset(&record_name, RecordType::TXT)];
+        updates.extend(values.iter().map(|value| {
+            Record::from_rdata(
+                record_name.clone(),
+                ttl,
+                RData::TXT(TXT::new(vec![value.clone()])),
+            )
+        }));
+
+        self.send_update(name, updates).await?;
+        log::info!("Set the TXT records of {} to {:?}", name, values);
+        Ok(None)
+    }
+
+    async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult {
+        let name = to_name(domain);
+        self.send_update(
+            domain,
+            vec![
+                delete_rrset(&name, RecordType::A),
+                delete_rrset(&name, RecordType::AAAA),
+            ],
+        )
+        .await?;
+        log::info!("Deleted the address records of {}", domain);
+        Ok(None)
+    }
+
+    async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse> {
+        let ipv4 = self
+            .query(domain, RecordType::A)
+            .await?
+            .iter()
+            .find_map(|r| r.data().and_then(|d| d.as_a()).map(|a| a.0));
+        let ipv6 = self
+            .query(domain, RecordType::AAAA)
+            .await?
+            .iter()
+            .find_map(|r| r.data().and_then(|d| d.as_aaaa()).map(|aaaa| aaaa.0));
+
+        Ok(Addresses { ipv4, ipv6 })
+    }
+}
+
+/// The UPDATE message of the zone, holding the records of its update section
+fn update_message(zone: &Name, updates: Vec<Record>) -> Message {
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Update)
+        .set_recursion_desired(false);
+
+    let mut zone_query = Query::new();
+    zone_query
+        .set_name(zone.clone())
+        .set_query_class(DNSClass::IN)
+        .set_query_type(RecordType::SOA);
+    message.add_zone(zone_query);
+    message.add_updates(updates);
+
+    message
+}
+
+/// The update records replacing the A and/or AAAA record sets of the domains
+fn address_updates(domains: &[FQDN], addresses: Addresses, ttl: u32) -> Vec<Record> {
+    let mut updates: Vec<Record> = Vec::with_capacity(4 * domains.len());
+
+    for domain in domains {
+        let name = to_name(domain);
+
+        if let Some(ipv4) = addresses.ipv4 {
+            updates.push(delete_rrset(&name, RecordType::A));
+            updates.push(Record::from_rdata(name.clone(), ttl, RData::A(A(ipv4))));
+        }
+
+        if let Some(ipv6) = addresses.ipv6 {
+            updates.push(delete_rrset(&name, RecordType::AAAA));
+            updates.push(Record::from_rdata(
+                name.clone(),
+                ttl,
+                RData::AAAA(AAAA(ipv6)),
+            ));
+        }
+    }
+
+    updates
+}
+
+/// The update record deleting the whole record set of the type at the name
+fn delete_rrset(name: &Name, record_type: RecordType) -> Record {
+    let mut record = Record::with(name.clone(), record_type, 0);
+    record.set_dns_class(DNSClass::ANY);
+    record.set_data(Some(RData::NULL(NULL::new())));
+    record
+}
+
+fn to_name(domain: &FQDN) -> Name {
+    Name::from_ascii(format!("{domain}.")).unwrap()
+}
+
+fn with_default_port(server: &str) -> String {
+    if server.parse::<SocketAddr>().is_ok() {
+        return server.to_string();
+    }
+
+    match server.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, 53).to_string(),
+        Err(_) if !server.contains(':') => format!("{server}:53"),
+        Err(_) => server.to_string(),
+    }
+}
+
+/// Signs the message, sends it over TCP to the server of the zone and verifies the response
+async fn exchange(zone: &Rfc2136Zone, mut message: Message) -> Result<Message, ClientResponse> {
+    let unavailable = |e: &dyn std::fmt::Display| {
+        log::warn!("DNS exchange error with {}: {e}", zone.server);
+        ClientResponse::ServiceUnavailable(String::from(
+            "Failed to submit domain change to the DNS server.",
+        ))
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let mut verifier = message
+        .finalize(&zone.signer, now)
+        .map_err(|e| unavailable(&e))?
+        .expect("TSIG signing always yields a verifier");
+    let request = message.to_vec().map_err(|e| unavailable(&e))?;
+
+    let response = timeout(EXCHANGE_TIMEOUT, async {
+        let mut stream = TcpStream::connect(&zone.server).await?;
+        stream.write_u16(request.len() as u16).await?;
+        stream.write_all(&request).await?;
+
+        let length = stream.read_u16().await?;
+        let mut response = vec![0; length as usize];
+        stream.read_exact(&mut response).await?;
+
+        Ok::<Vec<u8>, std::io::Error>(response)
+    })
+    .await
+    .map_err(|e| unavailable(&e))?
+    .map_err(|e| unavailable(&e))?;
+
+    let response = match Message::from_vec(&response) {
+        // Errors about the signature itself come back unsigned
+        Ok(m) if m.response_code() == ResponseCode::NotAuth && m.signature().is_empty() => {
+            log::warn!("The server {} rejected the TSIG key", zone.server);
+            return Err(ClientResponse::ServiceUnavailable(String::from(
+                "The DNS server rejected the TSIG key.",
+            )));
+        }
+        Ok(_) => verifier(&response).map_err(|e| unavailable(&e))?,
+        Err(e) => return Err(unavailable(&e)),
+    };
+
+    if response.id() != message.id() {
+        return Err(unavailable(&"mismatched response ID"));
+    }
+
+    Ok(response.into_message())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    fn fqdn(name: &str) -> FQDN {
+        FQDN::from_str(name).unwrap()
+    }
+
+    /// Builds the UPDATE of an A and AAAA upsert and decodes it back from the wire
+    fn upsert_on_the_wire() -> (Message, Vec<u8>) {
+        let addresses = Addresses {
+            ipv4: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            ipv6: Some("2001:db8::1".parse::<Ipv6Addr>().unwrap()),
+        };
+        let message = update_message(
+            &to_name(&fqdn("dyn.example.com")),
+            address_updates(&[fqdn("host.dyn.example.com")], addresses, 300),
+        );
+
+        let bytes = message.to_vec().unwrap();
+        (Message::from_vec(&bytes).unwrap(), bytes)
+    }
+
+    #[test]
+    fn update_header_and_zone_section() {
+        let (message, _) = upsert_on_the_wire();
+
+        assert_eq!(message.op_code(), OpCode::Update);
+        assert_eq!(message.message_type(), MessageType::Query);
+        assert!(!message.recursion_desired());
+
+        // The zone section of an UPDATE is carried in the question section
+        let zones = message.queries();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(
+            zones[0].name(),
+            &Name::from_ascii("dyn.example.com.").unwrap()
+        );
+        assert_eq!(zones[0].query_type(), RecordType::SOA);
+        assert_eq!(zones[0].query_class(), DNSClass::IN);
+    }
+
+    #[test]
+    fn upsert_deletes_then_adds_each_rrset() {
+        let (message, _) = upsert_on_the_wire();
+        let name = Name::from_ascii("host.dyn.example.com.").unwrap();
+
+        // The update section is carried in the authority section
+        let updates = message.name_servers();
+        assert_eq!(updates.len(), 4);
+
+        for (delete, record_type) in [
+            (&updates[0], RecordType::A),
+            (&updates[2], RecordType::AAAA),
+        ] {
+            assert_eq!(delete.name(), &name);
+            assert_eq!(delete.record_type(), record_type);
+            assert_eq!(delete.dns_class(), DNSClass::ANY);
+            assert_eq!(delete.ttl(), 0);
+            assert!(delete.data().is_none_or(|data| data
+                .as_null()
+                .is_some_and(|null| null.anything().is_empty())));
+        }
+
+        assert_eq!(updates[1].name(), &name);
+        assert_eq!(updates[1].dns_class(), DNSClass::IN);
+        assert_eq!(updates[1].ttl(), 300);
+        assert_eq!(
+            updates[1].data().and_then(RData::as_a),
+            Some(&A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+
+        assert_eq!(updates[3].name(), &name);
+        assert_eq!(updates[3].dns_class(), DNSClass::IN);
+        assert_eq!(updates[3].ttl(), 300);
+        assert_eq!(
+            updates[3].data().and_then(RData::as_aaaa),
+            Some(&AAAA("2001:db8::1".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn rrset_deletes_have_an_empty_rdata() {
+        let (_, bytes) = upsert_on_the_wire();
+
+        // TYPE A, CLASS ANY, TTL 0, RDLENGTH 0, right after the owner name of the first update
+        let delete_a: [u8; 10] = [0, 1, 0, 255, 0, 0, 0, 0, 0, 0];
+        assert!(bytes.windows(delete_a.len()).any(|w| w == delete_a));
+
+        // Same for TYPE AAAA
+        let delete_aaaa: [u8; 10] = [0, 28, 0, 255, 0, 0, 0, 0, 0, 0];
+        assert!(bytes.windows(delete_aaaa.len()).any(|w| w == delete_aaaa));
+    }
+
+    #[test]
+    fn update_is_signed_with_tsig() {
+        let signer = TSigner::new(
+            b"0123456789abcdef0123456789abcdef".to_vec(),
+            TsigAlgorithm::HmacSha256,
+            Name::from_ascii("ddns-key.").unwrap(),
+            TSIG_FUDGE,
+        )
+        .unwrap();
+
+        let mut message = update_message(
+            &to_name(&fqdn("dyn.example.com")),
+            address_updates(
+                &[fqdn("host.dyn.example.com")],
+                Addresses::from(Ipv4Addr::new(192, 0, 2, 1)),
+                300,
+            ),
+        );
+        message.finalize(&signer, 1_700_000_000).unwrap();
+
+        let decoded = Message::from_vec(&message.to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.signature().len(), 1);
+        assert_eq!(decoded.signature()[0].record_type(), RecordType::TSIG);
+        assert_eq!(
+            decoded.signature()[0].name(),
+            &Name::from_ascii("ddns-key.").unwrap()
+        );
+        assert_eq!(decoded.name_servers().len(), 2);
+    }
+}
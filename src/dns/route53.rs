@@ -0,0 +1,333 @@
+use std::{env, str::FromStr};
+
+use crate::{client_response::ClientResponse, ip::Addresses};
+use aws_config::Region;
+use aws_sdk_route53 as r53;
+use fqdn::FQDN;
+use fqdn_trie::FqdnTrieMap;
+use r53::{
+    error::ProvideErrorMetadata,
+    types::{Change, ChangeBatch, ResourceRecord, ResourceRecordSet, RrType},
+};
+
+use super::{join, ChangeResult, DnsBackend};
+
+macro_rules! unwrap_or_return {
+    ( $e:expr, $alt:expr ) => {
+        match $e {
+            Ok(x) => x,
+            Err(_) => return $alt,
+        }
+    };
+}
+
+pub struct Route53 {
+    client: r53::Client,
+    hosted_zone_map: FqdnTrieMap<FQDN, Option<String>>,
+    zones: Vec<FQDN>,
+}
+
+impl Route53 {
+    /// Connects to AWS and matches the hosted zones listed in `HOSTED_ZONE_ID_LIST`
+    pub async fn connect() -> Self {
+        let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(Region::new("ca-central-1"))
+            .load()
+            .await;
+
+        let client = r53::Client::new(&config);
+
+        let mut hosted_zone_search = client.list_hosted_zones();
+
+        if env::var("USE_PRIVATE_HOSTED_ZONE").is_ok_and(|v| !v.is_empty()) {
+            hosted_zone_search =
+                hosted_zone_search.hosted_zone_type(r53::types::HostedZoneType::PrivateHostedZone)
+        }
+
+        let result = hosted_zone_search
+            .send()
+            .await
+            .expect("Couldn't fetch the list of zones from AWS");
+
+        let mut hosted_zone_ids: Vec<String> = env::var("HOSTED_ZONE_ID_LIST")
+            .expect("HOSTED_ZONE_ID_LIST environment variable is not set.")
+            .split(';')
+            .map(|s| s.to_string())
+            .collect();
+
+        log::debug!(
+            "Received the following hosted zone IDs for dynamic dns service: {:?}",
+            hosted_zone_ids
+        );
+
+        let mut hosted_zone_map: FqdnTrieMap<FQDN, Option<String>> =
+            FqdnTrieMap::with_capacity(None, hosted_zone_ids.len());
+        let mut zones: Vec<FQDN> = Vec::with_capacity(hosted_zone_ids.len());
+
+        log::debug!(
+            "Received {} hosted zone from AWS",
+            result.hosted_zones.len()
+        );
+
+        for hz in result.hosted_zones {
+            log::debug!("AWS Hosted Zone name: \"{}\" with ID: {}", &hz.name, &hz.id);
+            if let Some(index) = hosted_zone_ids.iter().position(|i| hz.id.contains(i)) {
+                log::info!(
+                    "Domain zone \"{}\" available for services with hosted zone {}",
+                    &hz.name,
+                    &hz.id
+                );
+                hosted_zone_ids.remove(index);
+                let zone = FQDN::from_str(&hz.name).unwrap();
+                zones.push(zone.clone());
+                hosted_zone_map.insert(zone, Some(hz.id));
+            }
+        }
+
+        if !hosted_zone_ids.is_empty() {
+            log::error!("Couldn't match the following hosted zones: {hosted_zone_ids:?}");
+        }
+
+        hosted_zone_map.shrink_to_fit();
+
+        Route53 {
+            client,
+            hosted_zone_map,
+            zones,
+        }
+    }
+
+    fn upsert_change(domain: &FQDN, rr_type: RrType, values: Vec<String>, ttl: u32) -> Change {
+        let rr = ResourceRecordSet::builder()
+            .name(domain.to_string())
+            .r#type(rr_type)
+            .ttl(ttl.into())
+            .set_resource_records(Some(
+                values
+                    .into_iter()
+                    .map(|value| ResourceRecord::builder().value(value).build().unwrap())
+                    .collect(),
+            ))
+            .build()
+            .unwrap();
+
+        Change::builder()
+            .action(r53::types::ChangeAction::Upsert)
+            .resource_record_set(rr)
+            .build()
+            .unwrap()
+    }
+
+    /// Fetches the record sets of the domain with the types, given in the order Route 53 sorts them
+    async fn find_record_sets(
+        &self,
+        domain: &FQDN,
+        types: &[RrType],
+    ) -> Result<Vec<ResourceRecordSet>, ClientResponse> {
+        let hosted_zone_id = self
+            .hosted_zone_map
+            .lookup(domain)
+            .clone()
+            .ok_or_else(super::not_in_zone)?;
+
+        let output = self
+            .client
+            .list_resource_record_sets()
+            .hosted_zone_id(hosted_zone_id)
+            .start_record_name(domain.to_string())
+            .start_record_type(types[0].clone())
+            .max_items(types.len() as i32)
+            .send()
+            .await
+            .map_err(|e| {
+                log::warn!(
+                    "AWS list error: {}",
+                    e.message().unwrap_or("No error detail provided.")
+                );
+                ClientResponse::ServiceUnavailable(String::from(
+                    "Failed to fetch the records from Route53.",
+                ))
+            })?;
+
+        Ok(output
+            .resource_record_sets
+            .into_iter()
+            .filter(|rr| types.contains(&rr.r#type))
+            .filter(|rr| FQDN::from_str(&rr.name).is_ok_and(|name| &name == domain))
+            .collect())
+    }
+
+    #[allow(unreachable_code)]
+    #[allow(unused_variables)]
+    async fn send_request(&self, changes: Vec<Change>, domain: &FQDN) -> ChangeResult {
+        #[cfg(feature = "read_only_aws")]
+        {
+            log::error!(
+                "Would have submitted the following changes to AWS Route 53: {:?}",
+                changes
+            );
+
+            return Ok(None);
+        }
+        let output = unwrap_or_return!(
+        self.client
+        .change_resource_record_sets()
+        .hosted_zone_id(
+            unwrap_or_return!(
+                self.hosted_zone_map
+                    .lookup(domain)
+                    .clone()
+                    .ok_or(()),
+                Err(ClientResponse::NotAcceptable(String::from("The domain requested is not in any hosted zone that is enabled for dynamic DNS.")))
+            )
+        )
+        .change_batch(
+            ChangeBatch::builder()
+                .set_changes(Some(changes))
+                .build()
+                .unwrap()
+        )
+        .send()
+        .await
+        .inspect_err(|e| { log::warn!("AWS change error: {}", e.message().unwrap_or("No error detail provided.")); }),
+        Err(ClientResponse::ServiceUnavailable(String::from("Failed to submit domain change to Route53.")))
+    );
+        Ok(output.change_info().map(|info| info.id().to_string()))
+    }
+}
+
+#[rocket::async_trait]
+impl DnsBackend for Route53 {
+    fn name(&self) -> &'static str {
+        "AWS Route 53"
+    }
+
+    fn zones(&self) -> Vec<FQDN> {
+        self.zones.clone()
+    }
+
+    #[inline]
+    fn domain_included(&self, domain: &FQDN) -> bool {
+        self.hosted_zone_map.lookup(domain).is_some()
+    }
+
+    /// Upserts the A and/or AAAA records of the domains in a single change batch
+    async fn upsert_address_records(
+        &self,
+        domains: &[FQDN],
+        addresses: Addresses,
+        ttl: u32,
+    ) -> ChangeResult {
+        let Some(first) = domains.first() else {
+            return Ok(None);
+        };
+        let mut changes: Vec<Change> = Vec::with_capacity(2 * domains.len());
+
+        for domain in domains {
+            if let Some(ipv4) = addresses.ipv4 {
+                changes.push(Self::upsert_change(
+                    domain,
+                    RrType::A,
+                    vec![ipv4.to_string()],
+                    ttl,
+                ));
+            }
+
+            if let Some(ipv6) = addresses.ipv6 {
+                changes.push(Self::upsert_change(
+                    domain,
+                    RrType::Aaaa,
+                    vec![ipv6.to_string()],
+                    ttl,
+                ));
+            }
+        }
+
+        let change_id = self.send_request(changes, first).await?;
+        log::info!(
+            "Updated {} to IP {} with TTL {}",
+            join(domains),
+            addresses,
+            ttl
+        );
+        Ok(change_id)
+    }
+
+    /// Upserts the TXT record set of the name, or deletes the current one when there are no values
+    async fn set_txt_records(&self, name: &FQDN, values: &[String], ttl: u32) -> ChangeResult {
+        let changes: Vec<Change> = if values.is_empty() {
+            self.find_record_sets(name, &[RrType::Txt])
+                .await?
+                .into_iter()
+                .map(|rr| {
+                    Change::builder()
+                        .action(r53::types::ChangeAction::Delete)
+                        .resource_record_set(rr)
+                        .build()
+                        .unwrap()
+                })
+                .collect()
+        } else {
+            vec![Self::upsert_change(
+                name,
+                RrType::Txt,
+                values.iter().map(|value| format!("\"{value}\"")).collect(),
+                ttl,
+            )]
+        };
+
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let change_id = self.send_request(changes, name).await?;
+        log::info!("Set the TXT records of {} to {:?}", name, values);
+        Ok(change_id)
+    }
+
+    async fn delete_address_records(&self, domain: &FQDN) -> ChangeResult {
+        let changes: Vec<Change> = self
+            .find_record_sets(domain, &[RrType::A, RrType::Aaaa])
+            .await?
+            .into_iter()
+            .map(|rr| {
+                Change::builder()
+                    .action(r53::types::ChangeAction::Delete)
+                    .resource_record_set(rr)
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let change_id = self.send_request(changes, domain).await?;
+        log::info!("Deleted the address records of {}", domain);
+        Ok(change_id)
+    }
+
+    async fn lookup_address_records(&self, domain: &FQDN) -> Result<Addresses, ClientResponse> {
+        let mut addresses = Addresses::default();
+
+        for rr in self
+            .find_record_sets(domain, &[RrType::A, RrType::Aaaa])
+            .await?
+        {
+            let value = rr
+                .resource_records
+                .as_ref()
+                .and_then(|records| records.first())
+                .map(|record| record.value.as_str());
+
+            match rr.r#type {
+                RrType::A => addresses.ipv4 = value.and_then(|v| v.parse().ok()),
+                RrType::Aaaa => addresses.ipv6 = value.and_then(|v| v.parse().ok()),
+                _ => {}
+            }
+        }
+
+        Ok(addresses)
+    }
+}
@@ -0,0 +1,271 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use fqdn::FQDN;
+use rocket::{
+    http::{ContentType, Status},
+    response::{self, Responder},
+    Request, Response,
+};
+
+use crate::{client_response::ClientResponse, ip::Addresses, update::UpdateOutcome};
+
+/// A DynDNS2 protocol return code
+///
+/// Each hostname of a request yields one return code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DynDnsCode {
+    /// The update was successful and the record was changed
+    Good(Addresses),
+    /// The update was successful but the record already had the addresses
+    NoChg(Addresses),
+    /// The credentials provided are invalid
+    BadAuth,
+    /// The hostname provided is not a valid fully qualified domain name
+    NotFqdn,
+    /// The hostname provided does not belong to the account
+    NoHost,
+    /// Too many hostnames were provided in the request
+    NumHost,
+    /// The account is blocked from updating
+    Abuse,
+    /// The DNS provider refused the change
+    DnsErr,
+    /// A server side error prevented the update
+    ServerError,
+}
+
+impl DynDnsCode {
+    /// Translates the outcome of a DNS update into its DynDNS2 return code
+    pub fn from_outcome(outcome: &UpdateOutcome, addresses: Addresses) -> Self {
+        match outcome {
+            UpdateOutcome::Unchanged => Self::NoChg(addresses),
+            UpdateOutcome::Applied => Self::Good(addresses),
+            UpdateOutcome::Failed(ClientResponse::NotAcceptable(_)) => Self::NoHost,
+            UpdateOutcome::Failed(ClientResponse::BadRequest(_)) => Self::DnsErr,
+            UpdateOutcome::Failed(ClientResponse::Unauthorized(_)) => Self::BadAuth,
+            UpdateOutcome::Failed(_) => Self::ServerError,
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            Self::BadAuth => Status::Unauthorized,
+            Self::ServerError => Status::InternalServerError,
+            _ => Status::Ok,
+        }
+    }
+}
+
+impl fmt::Display for DynDnsCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Good(addresses) => write!(f, "good {}", DynDnsAddresses(addresses)),
+            Self::NoChg(addresses) => write!(f, "nochg {}", DynDnsAddresses(addresses)),
+            Self::BadAuth => write!(f, "badauth"),
+            Self::NotFqdn => write!(f, "notfqdn"),
+            Self::NoHost => write!(f, "nohost"),
+            Self::NumHost => write!(f, "numhost"),
+            Self::Abuse => write!(f, "abuse"),
+            Self::DnsErr => write!(f, "dnserr"),
+            Self::ServerError => write!(f, "911"),
+        }
+    }
+}
+
+/// The addresses as written after `good` and `nochg`, comma separated
+struct DynDnsAddresses<'a>(&'a Addresses);
+
+impl fmt::Display for DynDnsAddresses<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0.ipv4, self.0.ipv6) {
+            (Some(v4), Some(v6)) => write!(f, "{v4},{v6}"),
+            (Some(v4), None) => write!(f, "{v4}"),
+            (None, Some(v6)) => write!(f, "{v6}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The plain text response of a DynDNS2 update, one return code per line
+pub struct DynDnsResponse(Vec<DynDnsCode>);
+
+impl DynDnsResponse {
+    pub fn new(codes: Vec<DynDnsCode>) -> Self {
+        Self(codes)
+    }
+
+    pub fn single(code: DynDnsCode) -> Self {
+        Self(vec![code])
+    }
+}
+
+impl<'r> Responder<'r, 'static> for DynDnsResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self
+            .0
+            .iter()
+            .map(DynDnsCode::status)
+            .max_by_key(|s| s.code)
+            .unwrap_or(Status::Ok);
+
+        let body = self
+            .0
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Response::build_from(body.respond_to(request)?)
+            .status(status)
+            .header(ContentType::Plain)
+            .ok()
+    }
+}
+
+/// Maximum number of hostnames accepted in a single request
+pub const MAX_HOSTNAMES: usize = 20;
+
+/// Parses the `hostname` parameter, a comma separated list of hostnames
+///
+/// Invalid hostnames are kept as errors so each one can be answered with `notfqdn`.
+pub fn parse_hostnames(hostname: &str) -> Vec<Result<FQDN, ()>> {
+    hostname
+        .split(',')
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .map(|h| {
+            if !h.contains('.') {
+                return Err(());
+            }
+            FQDN::from_str(h).map_err(|_| ())
+        })
+        .collect()
+}
+
+/// Parses the `myip` and `myipv6` parameters
+///
+/// Both parameters can hold a comma separated list of addresses, which are sorted
+/// between IPv4 and IPv6. Following the DynDNS2 protocol, malformed addresses are ignored.
+pub fn parse_myip(myip: Option<&str>, myipv6: Option<&str>) -> Addresses {
+    let mut addresses = Addresses::default();
+
+    for value in myip
+        .into_iter()
+        .chain(myipv6)
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+    {
+        match value.parse::<IpAddr>().map(|ip| ip.to_canonical()) {
+            Ok(IpAddr::V4(v4)) if addresses.ipv4.is_none() => addresses.ipv4 = Some(v4),
+            Ok(IpAddr::V6(v6)) if addresses.ipv6.is_none() => addresses.ipv6 = Some(v6),
+            _ => log::debug!("Ignored IP parameter value \"{value}\""),
+        }
+    }
+
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use rocket::local::blocking::Client;
+
+    use super::*;
+
+    fn respond(codes: Vec<DynDnsCode>) -> (Status, String) {
+        let client = Client::untracked(rocket::build()).unwrap();
+        let request = client.get("/nic/update");
+        let mut response = DynDnsResponse::new(codes).respond_to(&request).unwrap();
+        let body = rocket::async_test(response.body_mut().to_string()).unwrap();
+
+        (response.status(), body)
+    }
+
+    #[test]
+    fn parses_multi_host_lines() {
+        let hostnames = parse_hostnames("a.example.com, b.example.com,,c.example.com.");
+
+        assert_eq!(
+            hostnames,
+            vec![
+                Ok(FQDN::from_str("a.example.com").unwrap()),
+                Ok(FQDN::from_str("b.example.com").unwrap()),
+                Ok(FQDN::from_str("c.example.com").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_bare_labels() {
+        let hostnames = parse_hostnames("router,a.example.com,bad..example.com");
+
+        assert_eq!(hostnames[0], Err(()));
+        assert!(hostnames[1].is_ok());
+        assert_eq!(hostnames[2], Err(()));
+        assert_eq!(DynDnsCode::NotFqdn.to_string(), "notfqdn");
+    }
+
+    #[test]
+    fn sorts_mixed_myip_lists() {
+        let addresses = parse_myip(Some("2001:db8::1, 192.0.2.1,192.0.2.2,nope"), None);
+
+        assert_eq!(addresses.ipv4, Some(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(addresses.ipv6, Some("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn myipv6_completes_myip() {
+        let addresses = parse_myip(Some("192.0.2.1"), Some("2001:db8::2"));
+
+        assert_eq!(addresses.ipv4, Some(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(
+            addresses.ipv6,
+            Some("2001:db8::2".parse::<Ipv6Addr>().unwrap())
+        );
+    }
+
+    #[test]
+    fn canonicalises_mapped_ipv4() {
+        let addresses = parse_myip(Some("::ffff:192.0.2.7"), None);
+
+        assert_eq!(addresses.ipv4, Some(Ipv4Addr::new(192, 0, 2, 7)));
+        assert_eq!(addresses.ipv6, None);
+    }
+
+    #[test]
+    fn writes_one_code_per_line() {
+        let addresses = Addresses {
+            ipv4: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            ipv6: Some("2001:db8::1".parse().unwrap()),
+        };
+
+        let (status, body) = respond(vec![
+            DynDnsCode::Good(addresses),
+            DynDnsCode::NoChg(Addresses::from(Ipv4Addr::new(192, 0, 2, 1))),
+            DynDnsCode::NoHost,
+        ]);
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body, "good 192.0.2.1,2001:db8::1\nnochg 192.0.2.1\nnohost");
+    }
+
+    #[test]
+    fn badauth_is_unauthorized() {
+        let (status, body) = respond(vec![DynDnsCode::BadAuth]);
+
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(body, "badauth");
+    }
+
+    #[test]
+    fn server_error_wins_over_success() {
+        let (status, body) = respond(vec![
+            DynDnsCode::Good(Addresses::from(Ipv4Addr::new(192, 0, 2, 1))),
+            DynDnsCode::ServerError,
+        ]);
+
+        assert_eq!(status, Status::InternalServerError);
+        assert_eq!(body, "good 192.0.2.1\n911");
+    }
+}
@@ -0,0 +1,265 @@
+use std::{env, net::IpAddr, time::Duration};
+
+use chrono::Utc;
+use rocket::{
+    fairing::AdHoc,
+    request::{self, FromRequest},
+    serde::Serialize,
+    FromFormField, Request,
+};
+use sea_orm::{entity::prelude::*, QueryOrder, Set};
+
+use crate::ip::Addresses;
+
+/// Default number of days the update history is kept
+const DEFAULT_RETENTION_DAYS: i64 = 90;
+
+/// Interval between two prunings of the update history
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How the client authenticated the update
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, FromFormField,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum AuthMethod {
+    #[sea_orm(string_value = "password")]
+    Password,
+    #[sea_orm(string_value = "signing")]
+    Signing,
+}
+
+/// Whether the update was written to the DNS provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum UpdateStatus {
+    #[sea_orm(string_value = "applied")]
+    Applied,
+    #[sea_orm(string_value = "rejected")]
+    Rejected,
+}
+
+/// An update applied or rejected for a domain
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "update_history")]
+#[serde(crate = "rocket::serde")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    /// The domain name of the record
+    pub domain: String,
+    /// When the update was received
+    pub created_at: DateTimeUtc,
+    /// If the update was applied or rejected
+    pub status: UpdateStatus,
+    /// The addresses known for the domain before the update
+    pub old_ip: Option<String>,
+    /// The addresses requested by the update
+    pub new_ip: Option<String>,
+    /// How the client authenticated
+    pub auth_method: AuthMethod,
+    /// The IP the request came from
+    pub source_address: String,
+    /// The user agent of the client
+    pub user_agent: Option<String>,
+    /// The ID of the change submitted to the DNS backend
+    pub change_id: Option<String>,
+    /// Why the update was rejected
+    pub reason: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Where an update request comes from
+pub struct RequestOrigin {
+    pub address: IpAddr,
+    pub user_agent: Option<String>,
+}
+
+impl RequestOrigin {
+    pub fn of(request: &Request<'_>) -> Option<Self> {
+        request.client_ip().map(|ip| Self {
+            address: ip.to_canonical(),
+            user_agent: request.headers().get_one("User-Agent").map(String::from),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestOrigin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        match Self::of(request) {
+            None => request::Outcome::Error((rocket::http::Status::BadRequest, ())),
+            Some(origin) => request::Outcome::Success(origin),
+        }
+    }
+}
+
+/// An update requested for a domain, to be written to the history
+pub struct UpdateAttempt<'a> {
+    pub domain: &'a str,
+    pub auth_method: AuthMethod,
+    pub origin: &'a RequestOrigin,
+}
+
+impl UpdateAttempt<'_> {
+    pub async fn applied(
+        &self,
+        db: &DbConn,
+        old: Option<Addresses>,
+        new: Addresses,
+        change_id: Option<String>,
+    ) {
+        self.save(db, UpdateStatus::Applied, old, Some(new), change_id, None)
+            .await
+    }
+
+    pub async fn rejected(&self, db: &DbConn, new: Option<Addresses>, reason: &str) {
+        self.save(db, UpdateStatus::Rejected, None, new, None, Some(reason))
+            .await
+    }
+
+    async fn save(
+        &self,
+        db: &DbConn,
+        status: UpdateStatus,
+        old: Option<Addresses>,
+        new: Option<Addresses>,
+        change_id: Option<String>,
+        reason: Option<&str>,
+    ) {
+        let entry = ActiveModel {
+            domain: Set(self.domain.trim_end_matches('.').to_lowercase()),
+            created_at: Set(Utc::now()),
+            status: Set(status),
+            old_ip: Set(old.map(|a| a.to_string())),
+            new_ip: Set(new.map(|a| a.to_string())),
+            auth_method: Set(self.auth_method),
+            source_address: Set(self.origin.address.to_string()),
+            user_agent: Set(self.origin.user_agent.clone()),
+            change_id: Set(change_id),
+            reason: Set(reason.map(String::from)),
+            ..Default::default()
+        };
+
+        if let Err(e) = entry.insert(db).await {
+            log::error!("Couldn't save the update history of {}: {e}", self.domain);
+        }
+    }
+}
+
+/// Writes an update rejected while authenticating the request to the history
+pub async fn record_rejection(
+    request: &Request<'_>,
+    domain: &str,
+    auth_method: AuthMethod,
+    reason: &str,
+) {
+    let (Some(db), Some(origin)) = (
+        request.rocket().state::<DbConn>(),
+        RequestOrigin::of(request),
+    ) else {
+        return;
+    };
+
+    UpdateAttempt {
+        domain,
+        auth_method,
+        origin: &origin,
+    }
+    .rejected(db, None, reason)
+    .await
+}
+
+/// A page of the update history of a domain, most recent first
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryPage {
+    pub domain: String,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+    pub entries: Vec<Model>,
+}
+
+pub async fn find_by_domain(
+    domain: &str,
+    page: u64,
+    per_page: u64,
+    db: &DbConn,
+) -> Result<HistoryPage, DbErr> {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    let paginator = Entity::find()
+        .filter(Column::Domain.eq(&domain))
+        .order_by_desc(Column::CreatedAt)
+        .order_by_desc(Column::Id)
+        .paginate(db, per_page);
+
+    let total = paginator.num_items().await?;
+    let entries = paginator.fetch_page(page.saturating_sub(1)).await?;
+
+    Ok(HistoryPage {
+        domain,
+        page,
+        per_page,
+        total,
+        entries,
+    })
+}
+
+async fn prune(retention: chrono::Duration, db: &DbConn) {
+    match Entity::delete_many()
+        .filter(Column::CreatedAt.lt(Utc::now() - retention))
+        .exec(db)
+        .await
+    {
+        Ok(result) if result.rows_affected > 0 => {
+            log::info!("Pruned {} update history entries", result.rows_affected)
+        }
+        Ok(_) => {}
+        Err(e) => log::error!("Couldn't prune the update history: {e}"),
+    }
+}
+
+/// Periodically prunes the update history older than `HISTORY_RETENTION_DAYS`
+///
+/// Setting the retention to 0 keeps the history forever.
+pub fn stage() -> AdHoc {
+    AdHoc::on_liftoff("Update history pruning", |rocket| {
+        Box::pin(async move {
+            let retention_days: i64 = env::var("HISTORY_RETENTION_DAYS")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("HISTORY_RETENTION_DAYS must be a number of days.")
+                })
+                .unwrap_or(DEFAULT_RETENTION_DAYS);
+
+            if retention_days <= 0 {
+                log::info!("Update history is kept forever");
+                return;
+            }
+
+            let db = rocket
+                .state::<DbConn>()
+                .expect("Must mount the database before pruning the history.")
+                .clone();
+            let retention = chrono::Duration::days(retention_days);
+
+            rocket::tokio::spawn(async move {
+                let mut interval = rocket::tokio::time::interval(PRUNE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    prune(retention, &db).await;
+                }
+            });
+        })
+    })
+}
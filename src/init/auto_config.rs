@@ -6,28 +6,36 @@ use fqdn::FQDN;
 use reqwest::header::HeaderMap;
 use rocket::fairing::AdHoc;
 
-use crate::route53::Route53;
+use crate::{dns::Dns, ip::Addresses, ttl::TtlPolicy};
 
 pub fn autoset_dns() -> AdHoc {
     AdHoc::on_ignite("DNS auto-configuration", |rocket| {
         Box::pin(async move {
-            let route53 = rocket
-                .state::<Route53>()
-                .expect("Must initialize Route53 before configuring DNS.");
+            let dns = rocket
+                .state::<Dns>()
+                .expect("Must initialize the DNS backend before configuring DNS.");
+            let ttls = rocket
+                .state::<TtlPolicy>()
+                .expect("Must load the TTL policy before configuring DNS.");
+            let ttl = |domain: &FQDN| ttls.resolve(None, &dns.zone(domain).unwrap());
 
             let web_client = reqwest::Client::new();
             let imds_token = get_imds_token(&web_client).await;
 
             if let Some(private_domain) = get_private_domain() {
-                if !route53.domain_included(&private_domain) {
+                if !dns.domain_included(&private_domain) {
                     panic!("The domain name \"{private_domain}\" isn't available in the current configuration.")
                 }
                 let private_ip = get_private_ip(&web_client, &imds_token).await;
 
-                if !route53
-                    .upsert_a_resource_record(private_domain.clone(), private_ip)
+                if dns
+                    .upsert_address_records(
+                        &private_domain,
+                        Addresses::from(private_ip),
+                        ttl(&private_domain),
+                    )
                     .await
-                    .is_ok()
+                    .is_err()
                 {
                     panic!("Failed to set {private_domain} to private IP.");
                 }
@@ -36,14 +44,18 @@ pub fn autoset_dns() -> AdHoc {
             }
 
             if let Some(public_domain) = get_public_domain() {
-                if !route53.domain_included(&public_domain) {
+                if !dns.domain_included(&public_domain) {
                     panic!("The domain name \"{public_domain}\" isn't available in the current configuration.")
                 }
                 if let Some(public_ip) = get_public_ip(&web_client, &imds_token).await {
-                    if !route53
-                        .upsert_a_resource_record(public_domain.clone(), public_ip)
+                    if dns
+                        .upsert_address_records(
+                            &public_domain,
+                            Addresses::from(public_ip),
+                            ttl(&public_domain),
+                        )
                         .await
-                        .is_ok()
+                        .is_err()
                     {
                         panic!("Failed to set {public_domain} to public IP.");
                     }
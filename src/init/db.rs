@@ -8,7 +8,7 @@ use sea_orm::DbErr;
 use sea_orm::Set;
 use std::env;
 
-use crate::account::AdminAccountActiveModel;
+use crate::account::{AdminAccountActiveModel, AdminRole, SUPER_ADMIN};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2,
@@ -62,8 +62,11 @@ async fn bootstrap_admin_user(db: &DbConn, admin_user_password: &str) {
         .to_string();
 
     let user = AdminAccountActiveModel {
-        user: Set(String::from("admin")),
+        user: Set(String::from(SUPER_ADMIN)),
         password_hash: Set(password_hash),
+        disabled: Set(Some(false)),
+        role: Set(AdminRole::SuperAdmin),
+        zones: Set(None),
     };
 
     match user.insert(db).await {
@@ -1,14 +1,17 @@
 use std::fmt;
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
 use rocket::request::FromRequest;
 use rocket::*;
 
-pub struct IP(Ipv4Addr);
+/// The IP address of the client making the request
+///
+/// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) are treated as IPv4.
+pub struct IP(IpAddr);
 
 impl IP {
-    pub fn get(&self) -> Ipv4Addr {
+    pub fn get(&self) -> IpAddr {
         self.0
     }
 }
@@ -23,8 +26,8 @@ impl FromStr for IP {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let ipv4 = s.parse::<Ipv4Addr>().map_err(|_| ())?;
-        Ok(Self(ipv4))
+        let ip = s.parse::<IpAddr>().map_err(|_| ())?;
+        Ok(Self(ip.to_canonical()))
     }
 }
 
@@ -34,12 +37,143 @@ impl<'r> FromRequest<'r> for IP {
     async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, ()> {
         match request.client_ip() {
             None => request::Outcome::Error((http::Status::BadRequest, ())),
-            Some(ip) => match ip {
-                std::net::IpAddr::V4(v4) => request::Outcome::Success(Self(v4)),
-                std::net::IpAddr::V6(_) => {
-                    request::Outcome::Error((http::Status::NotAcceptable, ()))
-                }
-            },
+            Some(ip) => request::Outcome::Success(Self(ip.to_canonical())),
+        }
+    }
+}
+
+/// The set of addresses to write for a domain
+///
+/// The IPv4 address is written to the A record and the IPv6 address to the AAAA record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Addresses {
+    pub ipv4: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+impl Addresses {
+    /// Parses a comma separated list holding at most one IPv4 and one IPv6 address
+    pub fn from_list(list: &str) -> Result<Self, ()> {
+        let mut addresses = Self::default();
+
+        for ip in list.split(',') {
+            match ip.trim().parse::<IpAddr>().map_err(|_| ())?.to_canonical() {
+                IpAddr::V4(v4) if addresses.ipv4.is_none() => addresses.ipv4 = Some(v4),
+                IpAddr::V6(v6) if addresses.ipv6.is_none() => addresses.ipv6 = Some(v6),
+                _ => return Err(()),
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ipv4.is_none() && self.ipv6.is_none()
+    }
+}
+
+impl From<IpAddr> for Addresses {
+    fn from(value: IpAddr) -> Self {
+        match value.to_canonical() {
+            IpAddr::V4(v4) => Self::from(v4),
+            IpAddr::V6(v6) => Self::from(v6),
+        }
+    }
+}
+
+impl From<Ipv4Addr> for Addresses {
+    fn from(value: Ipv4Addr) -> Self {
+        Self {
+            ipv4: Some(value),
+            ipv6: None,
+        }
+    }
+}
+
+impl From<Ipv6Addr> for Addresses {
+    fn from(value: Ipv6Addr) -> Self {
+        Self {
+            ipv4: None,
+            ipv6: Some(value),
+        }
+    }
+}
+
+impl fmt::Display for Addresses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.ipv4, self.ipv6) {
+            (Some(v4), Some(v6)) => write!(f, "{v4}, {v6}"),
+            (Some(v4), None) => write!(f, "{v4}"),
+            (None, Some(v6)) => write!(f, "{v6}"),
+            (None, None) => write!(f, "no address"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(s: &str) -> Option<Ipv4Addr> {
+        Some(s.parse().unwrap())
+    }
+
+    fn v6(s: &str) -> Option<Ipv6Addr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_a_single_address() {
+        let addresses = Addresses::from_list("203.0.113.7").unwrap();
+        assert_eq!(addresses.ipv4, v4("203.0.113.7"));
+        assert_eq!(addresses.ipv6, None);
+
+        let addresses = Addresses::from_list("2001:db8::1").unwrap();
+        assert_eq!(addresses.ipv4, None);
+        assert_eq!(addresses.ipv6, v6("2001:db8::1"));
+    }
+
+    #[test]
+    fn parses_both_families_in_any_order() {
+        let expected = Addresses {
+            ipv4: v4("203.0.113.7"),
+            ipv6: v6("2001:db8::1"),
+        };
+
+        assert_eq!(
+            Addresses::from_list("203.0.113.7,2001:db8::1"),
+            Ok(expected)
+        );
+        assert_eq!(
+            Addresses::from_list("2001:db8::1, 203.0.113.7"),
+            Ok(expected)
+        );
+        assert_eq!(
+            Addresses::from_list(" 203.0.113.7 , 2001:db8::1 "),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn maps_ipv4_mapped_addresses_to_ipv4() {
+        let addresses = Addresses::from_list("::ffff:203.0.113.7").unwrap();
+        assert_eq!(addresses.ipv4, v4("203.0.113.7"));
+        assert_eq!(addresses.ipv6, None);
+    }
+
+    #[test]
+    fn refuses_invalid_lists() {
+        for list in [
+            "",
+            "203.0.113.7,",
+            "203.0.113.7,198.51.100.1",
+            "2001:db8::1,2001:db8::2",
+            "203.0.113.7,::ffff:198.51.100.1",
+            "203.0.113.7;2001:db8::1",
+            "203.0.113.256",
+            "a.dyn.example.com",
+        ] {
+            assert_eq!(Addresses::from_list(list), Err(()), "{list}");
         }
     }
 }
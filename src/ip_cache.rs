@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    str::FromStr,
+    sync::RwLock,
+};
+
+use chrono::{DateTime, Utc};
+use fqdn::FQDN;
+use rocket::fairing::AdHoc;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
+
+use crate::{account::HostnamePattern, ip::Addresses};
+
+/// Default time after which a cached IP is written again even if it did not change
+const DEFAULT_MAX_AGE: i64 = 24 * 60 * 60;
+
+/// The last IP addresses applied to a domain
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "last_known_ip")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    /// The domain name of the record
+    pub domain: String,
+    /// The last IPv4 address written to the A record
+    pub ipv4: Option<String>,
+    /// The last IPv6 address written to the AAAA record
+    pub ipv6: Option<String>,
+    /// When the addresses were last written
+    pub updated_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CachedAddresses {
+    pub addresses: Addresses,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Model> for CachedAddresses {
+    fn from(value: Model) -> Self {
+        Self {
+            addresses: Addresses {
+                ipv4: value.ipv4.and_then(|ip| ip.parse().ok()),
+                ipv6: value.ipv6.and_then(|ip| ip.parse().ok()),
+            },
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+/// In-memory copy of the last known IP table
+///
+/// Used to skip the DNS writes when a domain is updated to the addresses it already has.
+pub struct IpCache {
+    entries: RwLock<HashMap<String, CachedAddresses>>,
+    /// The domains whose records must be written at their next update, even without a change
+    stale: RwLock<HashSet<String>>,
+    max_age: chrono::Duration,
+}
+
+impl IpCache {
+    fn key(domain: &str) -> String {
+        domain.trim_end_matches('.').to_lowercase()
+    }
+
+    pub fn get(&self, domain: &str) -> Option<CachedAddresses> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(&Self::key(domain))
+            .copied()
+    }
+
+    /// Whether every address requested is already applied to the domain
+    ///
+    /// Entries older than the maximum age are never considered unchanged, so the record
+    /// gets refreshed in case it was modified outside of the program.
+    pub fn is_unchanged(&self, domain: &str, addresses: &Addresses) -> bool {
+        if self.stale.read().unwrap().contains(&Self::key(domain)) {
+            return false;
+        }
+
+        let Some(cached) = self.get(domain) else {
+            return false;
+        };
+
+        if cached.updated_at + self.max_age < Utc::now() {
+            return false;
+        }
+
+        addresses
+            .ipv4
+            .is_none_or(|ip| cached.addresses.ipv4 == Some(ip))
+            && addresses
+                .ipv6
+                .is_none_or(|ip| cached.addresses.ipv6 == Some(ip))
+    }
+
+    /// Makes the next update of the domains write their records, such as after a change of TTL
+    ///
+    /// Only kept in memory, the records are written again anyway once their entry reaches the maximum age.
+    pub fn invalidate(&self, domains: &[String]) {
+        self.stale
+            .write()
+            .unwrap()
+            .extend(domains.iter().map(|domain| Self::key(domain)));
+    }
+
+    /// The cached domains matching the pattern, such as the names created under a subtree
+    pub fn domains_matching(&self, pattern: &HostnamePattern) -> Vec<FQDN> {
+        self.entries
+            .read()
+            .unwrap()
+            .keys()
+            .filter_map(|domain| FQDN::from_str(domain).ok())
+            .filter(|domain| pattern.matches(domain))
+            .collect()
+    }
+
+    /// Records the addresses applied to the domain, in memory and in the database
+    pub async fn store(
+        &self,
+        domain: &str,
+        addresses: Addresses,
+        db: &DbConn,
+    ) -> Result<(), DbErr> {
+        let key = Self::key(domain);
+        self.stale.write().unwrap().remove(&key);
+        let cached = {
+            let mut entries = self.entries.write().unwrap();
+            let entry = entries.entry(key.clone()).or_insert(CachedAddresses {
+                addresses: Addresses::default(),
+                updated_at: Utc::now(),
+            });
+            if addresses.ipv4.is_some() {
+                entry.addresses.ipv4 = addresses.ipv4;
+            }
+            if addresses.ipv6.is_some() {
+                entry.addresses.ipv6 = addresses.ipv6;
+            }
+            entry.updated_at = Utc::now();
+            *entry
+        };
+
+        let model = ActiveModel {
+            domain: Set(key),
+            ipv4: Set(cached.addresses.ipv4.map(|ip| ip.to_string())),
+            ipv6: Set(cached.addresses.ipv6.map(|ip| ip.to_string())),
+            updated_at: Set(cached.updated_at),
+        };
+
+        Entity::insert(model)
+            .on_conflict(
+                OnConflict::column(Column::Domain)
+                    .update_columns([Column::Ipv4, Column::Ipv6, Column::UpdatedAt])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forgets the addresses of the domain, in memory and in the database
+    pub async fn remove(&self, domain: &str, db: &DbConn) -> Result<(), DbErr> {
+        let key = Self::key(domain);
+        self.entries.write().unwrap().remove(&key);
+        self.stale.write().unwrap().remove(&key);
+        Entity::delete_by_id(key).exec(db).await?;
+
+        Ok(())
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Loading last known IP cache", |rocket| {
+        Box::pin(async move {
+            let db = rocket
+                .state::<DbConn>()
+                .expect("Must mount the database before loading the IP cache.");
+
+            let max_age: i64 = env::var("IP_CACHE_MAX_AGE")
+                .ok()
+                .map(|v| {
+                    v.parse()
+                        .expect("IP_CACHE_MAX_AGE must be a number of seconds.")
+                })
+                .unwrap_or(DEFAULT_MAX_AGE);
+
+            let entries: HashMap<String, CachedAddresses> = Entity::find()
+                .all(db)
+                .await
+                .expect("Couldn't load the last known IP cache from the database.")
+                .into_iter()
+                .map(|m| (m.domain.clone(), CachedAddresses::from(m)))
+                .collect();
+
+            log::info!("Loaded {} last known IP entries", entries.len());
+
+            rocket.manage(IpCache {
+                entries: RwLock::new(entries),
+                stale: RwLock::new(HashSet::new()),
+                max_age: chrono::Duration::seconds(max_age),
+            })
+        })
+    })
+}
@@ -1,9 +1,16 @@
 mod account;
+mod acme;
 mod client_response;
+mod dns;
+mod dyndns;
+mod history;
 mod init;
 mod ip;
-mod route53;
+mod ip_cache;
+mod replay;
 mod routes;
+mod ttl;
+mod update;
 mod utils;
 
 use std::net::{IpAddr, Ipv4Addr};
@@ -21,7 +28,12 @@ async fn rocket() -> Rocket<Build> {
 
     let rocket = rocket::custom(figment)
         .attach(init::db::stage())
-        .attach(route53::stage())
+        .attach(ip_cache::stage())
+        .attach(ttl::stage())
+        .attach(replay::stage())
+        .attach(account::signing_key::stage())
+        .attach(history::stage())
+        .attach(dns::stage())
         .attach(stage_rng())
         .attach(routes::secure_http::stage())
         .attach(routes::unsecure_http::stage())
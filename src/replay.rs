@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use rocket::fairing::AdHoc;
+
+/// Interval between two prunings of the expired messages
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The signed messages accepted recently, managed in Rocket's state
+///
+/// A signed request can be captured on a plain HTTP connection, so each signed message is only
+/// accepted once while its date is valid. Only kept in memory, the date check takes over after a restart.
+pub struct ReplayCache {
+    seen: Mutex<SeenMessages>,
+}
+
+struct SeenMessages {
+    /// The signed messages, with the time after which their date isn't accepted anymore
+    expiries: HashMap<String, DateTime<Utc>>,
+    pruned_at: Instant,
+}
+
+impl ReplayCache {
+    pub fn new() -> Self {
+        Self {
+            seen: Mutex::new(SeenMessages {
+                expiries: HashMap::new(),
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records the signed message, returns false when it was already accepted and didn't expire
+    ///
+    /// The expired messages are dropped at most once per `PRUNE_INTERVAL`.
+    pub fn accept(&self, message: &str, expires_at: DateTime<Utc>) -> bool {
+        let now = Utc::now();
+        let mut seen = self.seen.lock().unwrap();
+
+        if seen.pruned_at.elapsed() >= PRUNE_INTERVAL {
+            seen.expiries.retain(|_, expiry| *expiry >= now);
+            seen.pruned_at = Instant::now();
+        }
+
+        if seen
+            .expiries
+            .get(message)
+            .is_some_and(|expiry| *expiry >= now)
+        {
+            return false;
+        }
+
+        seen.expiries.insert(message.to_owned(), expires_at);
+        true
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Signature replay cache", |rocket| {
+        Box::pin(async { rocket.manage(ReplayCache::new()) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_message_until_it_expires() {
+        let cache = ReplayCache::new();
+        let expires_at = Utc::now() + chrono::Duration::milliseconds(50);
+
+        assert!(cache.accept("v2;date;a.dyn.example.com", expires_at));
+        assert!(!cache.accept("v2;date;a.dyn.example.com", expires_at));
+        assert!(cache.accept("v2;date;b.dyn.example.com", expires_at));
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(cache.accept(
+            "v2;date;a.dyn.example.com",
+            Utc::now() + chrono::Duration::seconds(60)
+        ));
+        assert!(!cache.accept(
+            "v2;date;a.dyn.example.com",
+            Utc::now() + chrono::Duration::seconds(60)
+        ));
+    }
+
+    #[test]
+    fn prunes_the_expired_messages_after_the_interval() {
+        let cache = ReplayCache::new();
+        let expired = Utc::now() - chrono::Duration::seconds(1);
+        let valid = Utc::now() + chrono::Duration::seconds(60);
+
+        cache.accept("expired", expired);
+        cache.accept("valid", valid);
+        cache.accept("other", valid);
+        assert_eq!(cache.seen.lock().unwrap().expiries.len(), 3);
+
+        cache.seen.lock().unwrap().pruned_at = Instant::now() - PRUNE_INTERVAL;
+        cache.accept("other", valid);
+
+        let seen = cache.seen.lock().unwrap();
+        assert!(!seen.expiries.contains_key("expired"));
+        assert!(seen.expiries.contains_key("valid"));
+        assert!(seen.pruned_at.elapsed() < PRUNE_INTERVAL);
+    }
+}
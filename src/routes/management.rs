@@ -1,52 +1,124 @@
-use std::sync::Mutex;
+use std::{
+    env,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    sync::Mutex,
+};
 
 use askama::Template;
-use fqdn::fqdn;
+use fqdn::FQDN;
 use rand::rngs::StdRng;
-use rocket::{data::ToByteUnit, fairing::AdHoc, get, post, routes, serde::json::Json, Data, State};
-use sea_orm::{ActiveModelTrait, DbConn};
+use rocket::{
+    data::ToByteUnit,
+    delete,
+    fairing::AdHoc,
+    get, post, routes,
+    serde::{json::Json, Deserialize, Serialize},
+    Data, FromFormField, State,
+};
+use sea_orm::{ActiveModelTrait, DbConn, DbErr};
 
 use crate::{
     account::{
-        self, AdminAccount, AdminAccountActiveModel, PasswordAccount, PublicKey, SigningAccount,
+        self,
+        admin_token::{self, MintedToken},
+        hostname,
+        ip_policy::{self, IpOverride, IpPolicy},
+        openssh,
+        signing_key::{self, KeyPolicy, SigningKeyView},
+        AccountFilter, AccountPage, AdminAccount, AdminAccountActiveModel, AdminRole, AdminSummary,
+        HostnamePattern, PasswordAccount, PublicKey, SigningAccount, SUPER_ADMIN,
     },
+    acme,
     client_response::ClientResponse,
-    route53::Route53,
+    dns::Dns,
+    history::{self, HistoryPage},
+    ip::Addresses,
+    ip_cache::IpCache,
+    ttl::TtlPolicy,
     utils::{generate_random_password, Credentials},
 };
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Mount management routes", |rocket| {
         Box::pin(async move {
-            rocket.mount(
+            rocket.manage(Parking::from_env()).mount(
                 "/mgmt",
-                routes![add_signing_domain, add_password_domain, new_admin],
+                routes![
+                    add_signing_domain,
+                    add_password_domain,
+                    new_admin,
+                    domain_history,
+                    list_zones,
+                    lookup_records,
+                    list_accounts,
+                    disable_account,
+                    enable_account,
+                    delete_account,
+                    rotate_password,
+                    rotate_signing_key,
+                    set_legacy_signatures,
+                    list_signing_keys,
+                    add_signing_key,
+                    revoke_signing_key,
+                    list_admins,
+                    delete_admin,
+                    reset_admin_password,
+                    change_own_password,
+                    disable_admin,
+                    enable_admin,
+                    set_admin_role,
+                    mint_token,
+                    list_tokens,
+                    revoke_token,
+                    list_hostnames,
+                    add_hostname,
+                    remove_hostname,
+                    set_account_ttl,
+                    get_ip_policy,
+                    set_ip_policy,
+                    list_zone_ttls,
+                    set_zone_ttl
+                ],
             )
         })
     })
 }
 
-#[get("/add-domain/password/<domain>")]
+#[get("/add-domain/password/<domain>?<ttl>")]
 async fn add_password_domain(
     domain: String,
+    ttl: Option<u32>,
     admin: AdminAccount,
-    route53: &State<Route53>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
     db: &State<DbConn>,
     rng: &State<Mutex<StdRng>>,
 ) -> ClientResponse {
-    let domain = domain.trim();
-    if !route53.inner().domain_included(&fqdn!(&domain)) {
-        ::log::warn!(
-            "The admin \"{}\" attempted to add the following domain, which is not supported: {}",
-            admin.get_user(),
-            domain
-        );
-        return ClientResponse::NotAcceptable(String::from(
-            "The domain name provided is not available for dynamic DNS.",
-        ));
+    let domain = match HostnamePattern::from_str(domain.trim()) {
+        Ok(pattern) if dns.inner().domain_included(pattern.fqdn()) => pattern.to_string(),
+        _ => {
+            ::log::warn!(
+                "The admin \"{}\" attempted to add the following domain, which is not supported: {}",
+                admin.get_user(),
+                domain.trim()
+            );
+            return ClientResponse::NotAcceptable(String::from(
+                "The domain name provided is not available for dynamic DNS.",
+            ));
+        }
+    };
+    let domain = domain.as_str();
+
+    if !admin.can_create(domain) {
+        return not_allowed(&admin, "add the domain", domain);
     }
 
-    match account::exists(domain, db.inner()).await {
+    if let Some(Err(response)) = ttl.map(|ttl| ttls.check(ttl)) {
+        return response;
+    }
+
+    match account::in_use(domain, db.inner()).await {
         Ok(exists) => {
             if exists {
                 ::log::warn!(
@@ -67,7 +139,7 @@ async fn add_password_domain(
 
     let (password, password_hash) = generate_random_password(rng.inner());
 
-    let _ = PasswordAccount::create_account(domain, &password_hash, &admin, db.inner()).await;
+    let _ = PasswordAccount::create_account(domain, &password_hash, &admin, ttl, db.inner()).await;
 
     ::log::warn!(
         "The admin \"{}\" added this new domain: {}",
@@ -81,27 +153,42 @@ async fn add_password_domain(
     )
 }
 
-#[post("/add-domain/signing/<domain>", data = "<signature>")]
+#[post("/add-domain/signing/<domain>?<ttl>", data = "<signature>")]
+#[allow(clippy::too_many_arguments)]
 async fn add_signing_domain(
     domain: String,
+    ttl: Option<u32>,
     signature: Data<'_>,
     admin: AdminAccount,
-    route53: &State<Route53>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    keys: &State<KeyPolicy>,
     db: &State<DbConn>,
 ) -> ClientResponse {
-    let domain = domain.trim();
-    if !route53.inner().domain_included(&fqdn!(&domain)) {
-        ::log::warn!(
-            "The admin \"{}\" attempted to add the following domain, which is not supported: {}",
-            admin.get_user(),
-            domain
-        );
-        return ClientResponse::NotAcceptable(String::from(
-            "The domain name provided is not available for dynamic DNS.",
-        ));
+    let domain = match HostnamePattern::from_str(domain.trim()) {
+        Ok(pattern) if dns.inner().domain_included(pattern.fqdn()) => pattern.to_string(),
+        _ => {
+            ::log::warn!(
+                "The admin \"{}\" attempted to add the following domain, which is not supported: {}",
+                admin.get_user(),
+                domain.trim()
+            );
+            return ClientResponse::NotAcceptable(String::from(
+                "The domain name provided is not available for dynamic DNS.",
+            ));
+        }
+    };
+    let domain = domain.as_str();
+
+    if !admin.can_create(domain) {
+        return not_allowed(&admin, "add the domain", domain);
     }
 
-    match account::exists(domain, db.inner()).await {
+    if let Some(Err(response)) = ttl.map(|ttl| ttls.check(ttl)) {
+        return response;
+    }
+
+    match account::in_use(domain, db.inner()).await {
         Ok(exists) => {
             if exists {
                 ::log::warn!(
@@ -120,10 +207,31 @@ async fn add_signing_domain(
         }
     }
 
+    let public_key = match read_public_key(signature, &admin, keys).await {
+        Ok(public_key) => public_key,
+        Err(response) => return response,
+    };
+
+    let _ = SigningAccount::create_account(domain, &public_key, &admin, ttl, db.inner()).await;
+
+    ::log::warn!(
+        "The admin \"{}\" added the domain \"{}\" with a public key",
+        admin.get_user(),
+        domain,
+    );
+    ClientResponse::Ok(String::default())
+}
+
+/// Reads the PEM or OpenSSH public key uploaded in the body of the request, refusing the ones below the key policy
+async fn read_public_key(
+    signature: Data<'_>,
+    admin: &AdminAccount,
+    keys: &KeyPolicy,
+) -> Result<PublicKey, ClientResponse> {
     let bytes = match signature.open(10.kilobytes()).into_bytes().await {
         Err(e) => {
             log::error!("Error streaming bytes from included file in request: {}", e);
-            return ClientResponse::InternalServerError("".to_string());
+            return Err(ClientResponse::InternalServerError("".to_string()));
         }
         Ok(v) => {
             if v.is_complete() {
@@ -133,52 +241,360 @@ async fn add_signing_domain(
                     "The admin \"{}\" attempted to upload a key with over 10KB in size",
                     admin.get_user()
                 );
-                return ClientResponse::NotAcceptable(
+                return Err(ClientResponse::NotAcceptable(
                     "The public key uploaded can't be over 10KB in size.".to_string(),
-                );
+                ));
             }
         }
     };
 
     if bytes.is_empty() {
         log::info!(
-            "The admin \"{}\" attempted to create a signing account without a public key.",
+            "The admin \"{}\" attempted to upload an empty public key.",
             admin.get_user()
         );
-        return ClientResponse::NotAcceptable(
+        return Err(ClientResponse::NotAcceptable(
             "The public key uploaded can't be empty.".to_string(),
+        ));
+    }
+
+    decode_public_key(&bytes, admin.get_user(), keys)
+}
+
+/// Parses a PEM or OpenSSH public key uploaded by the admin, and checks it against the key policy
+fn decode_public_key(
+    bytes: &[u8],
+    user: &str,
+    keys: &KeyPolicy,
+) -> Result<PublicKey, ClientResponse> {
+    let public_key = match std::str::from_utf8(bytes) {
+        Ok(line) if openssh::is_public_key_line(line) => openssh::parse_public_key(line),
+        _ => PublicKey::public_key_from_pem(bytes).ok(),
+    }
+    .ok_or_else(|| {
+        log::info!(
+            "The admin \"{}\" attempted to upload an invalid public key.",
+            user
         );
+        ClientResponse::NotAcceptable(
+            "The public key uploaded is not a valid PEM or OpenSSH key.".to_string(),
+        )
+    })?;
+
+    match keys.check(&public_key) {
+        Ok(_) => Ok(public_key),
+        Err(reason) => {
+            log::info!(
+                "The admin \"{}\" attempted to upload a public key refused by the key policy: {}",
+                user,
+                reason
+            );
+            Err(ClientResponse::NotAcceptable(reason))
+        }
+    }
+}
+
+/// Issues a new password for the password account of the domain
+#[post("/account/<domain>/rotate/password")]
+async fn rotate_password(
+    domain: &str,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+    rng: &State<Mutex<StdRng>>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    let (password, password_hash) = generate_random_password(rng.inner());
+
+    match PasswordAccount::set_password_hash(domain, &password_hash, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from(
+                "No password account exists for this domain.",
+            ))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" rotated the password of the domain {}",
+        admin.get_user(),
+        domain
+    );
+    ClientResponse::Ok(
+        CommandDownload::new(domain.to_string(), password)
+            .render()
+            .unwrap(),
+    )
+}
+
+/// Replaces every public key of the signing account of the domain with the one in the body
+#[post("/account/<domain>/rotate/signing", data = "<signature>")]
+async fn rotate_signing_key(
+    domain: &str,
+    signature: Data<'_>,
+    admin: AdminAccount,
+    keys: &State<KeyPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
     }
 
-    let public_key = PublicKey::public_key_from_pem(&bytes).unwrap();
+    let public_key = match read_public_key(signature, &admin, keys).await {
+        Ok(public_key) => public_key,
+        Err(response) => return response,
+    };
 
-    let _ = SigningAccount::create_account(domain, &public_key, &admin, db.inner()).await;
+    match SigningAccount::replace_keys(domain, &public_key, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from(
+                "No signing account exists for this domain.",
+            ))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
 
     ::log::warn!(
-        "The admin \"{}\" added the domain \"{}\" with a public key",
+        "The admin \"{}\" rotated the public key of the domain {}",
         admin.get_user(),
-        domain,
+        domain
     );
     ClientResponse::Ok(String::default())
 }
 
-#[post("/admin/new", data = "<credentials>")]
+/// Accepts or refuses the signatures that don't include the IP for the signing account of the domain
+#[post("/account/<domain>/legacy-signatures?<allowed>")]
+async fn set_legacy_signatures(
+    domain: &str,
+    allowed: bool,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match SigningAccount::set_legacy_signatures(domain, allowed, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from(
+                "No signing account exists for this domain.",
+            ))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" {} the legacy signatures of the domain {}",
+        admin.get_user(),
+        if allowed { "allowed" } else { "refused" },
+        domain
+    );
+    ClientResponse::Ok(String::from(if allowed {
+        "Legacy signatures allowed."
+    } else {
+        "Legacy signatures refused."
+    }))
+}
+
+async fn find_signing_account(domain: &str, db: &DbConn) -> Result<(), ClientResponse> {
+    match SigningAccount::exists(domain, db).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ClientResponse::NotFound(String::from(
+            "No signing account exists for this domain.",
+        ))),
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            Err(ClientResponse::InternalServerError(String::new()))
+        }
+    }
+}
+
+#[get("/account/<domain>/keys")]
+async fn list_signing_keys(
+    domain: &str,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<Vec<SigningKeyView>>, ClientResponse> {
+    let domain = domain.trim();
+    authorize(&admin, domain, Access::Read, db.inner()).await?;
+    find_signing_account(domain, db.inner()).await?;
+
+    signing_key::find_by_domain(domain, db.inner())
+        .await
+        .map(|keys| Json(keys.into_iter().map(SigningKeyView::from).collect()))
+        .map_err(|e| {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        })
+}
+
+/// Adds the public key in the body to the signing account of the domain, next to its other keys
+///
+/// The optional `not_after` is an RFC 3339 date after which the key is refused.
+#[post("/account/<domain>/keys?<label>&<not_after>", data = "<signature>")]
+async fn add_signing_key(
+    domain: &str,
+    label: Option<String>,
+    not_after: Option<&str>,
+    signature: Data<'_>,
+    admin: AdminAccount,
+    keys: &State<KeyPolicy>,
+    db: &State<DbConn>,
+) -> Result<Json<SigningKeyView>, ClientResponse> {
+    let domain = domain.trim();
+    authorize(&admin, domain, Access::Manage, db.inner()).await?;
+    find_signing_account(domain, db.inner()).await?;
+
+    let not_after = match not_after.map(chrono::DateTime::parse_from_rfc3339) {
+        None => None,
+        Some(Ok(date)) if date > chrono::Utc::now() => Some(date.to_utc()),
+        Some(_) => {
+            return Err(ClientResponse::BadRequest(String::from(
+                "The not_after date must be an RFC 3339 date in the future.",
+            )))
+        }
+    };
+
+    let public_key = read_public_key(signature, &admin, keys).await?;
+    let fingerprint = signing_key::fingerprint(&public_key);
+
+    let db_error = |e: sea_orm::DbErr| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    };
+
+    if signing_key::find_active(domain, db.inner())
+        .await
+        .map_err(db_error)?
+        .iter()
+        .any(|k| k.fingerprint == fingerprint)
+    {
+        return Err(ClientResponse::Conflict(String::from(
+            "This public key is already active for the domain.",
+        )));
+    }
+
+    let key = signing_key::add(domain, &public_key, label, not_after, db.inner())
+        .await
+        .map_err(db_error)?;
+
+    ::log::warn!(
+        "The admin \"{}\" added the key {} to the domain {}",
+        admin.get_user(),
+        key.fingerprint,
+        domain
+    );
+    Ok(Json(key.into()))
+}
+
+#[delete("/account/<domain>/keys/<id>")]
+async fn revoke_signing_key(
+    domain: &str,
+    id: i64,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match signing_key::revoke(domain, id, db.inner()).await {
+        Ok(true) => {
+            ::log::warn!(
+                "The admin \"{}\" revoked the key {} of the domain {}",
+                admin.get_user(),
+                id,
+                domain
+            );
+            ClientResponse::Ok(String::from("Key revoked."))
+        }
+        Ok(false) => ClientResponse::NotFound(String::from(
+            "No key with this ID is active for the domain.",
+        )),
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        }
+    }
+}
+
+/// The role given to an admin, along with the FQDN suffixes it is limited to
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RoleAssignment {
+    #[serde(default)]
+    role: AdminRole,
+    #[serde(default)]
+    zones: Vec<String>,
+}
+
+impl RoleAssignment {
+    fn zones(&self) -> Result<Vec<FQDN>, ClientResponse> {
+        parse_zones(&self.zones)
+    }
+}
+
+/// Parses the FQDN suffixes given to an admin or a token
+fn parse_zones(zones: &[String]) -> Result<Vec<FQDN>, ClientResponse> {
+    zones
+        .iter()
+        .map(|zone| {
+            FQDN::from_str(zone.trim()).map_err(|_| {
+                ClientResponse::BadRequest(format!(
+                    "The zone \"{zone}\" is not a valid domain name."
+                ))
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct NewAdmin {
+    #[serde(flatten)]
+    credentials: Credentials,
+    #[serde(flatten)]
+    assignment: RoleAssignment,
+}
+
+#[post("/admin/new", data = "<new_admin>")]
 async fn new_admin(
-    credentials: Json<Credentials>,
+    new_admin: Json<NewAdmin>,
     admin: AdminAccount,
     db: &State<DbConn>,
 ) -> ClientResponse {
-    if admin.get_user() != "admin" {
-        ::log::warn!(
-            "The user {} attempted to create an admin user, but is not allowed.",
-            admin.get_user()
-        );
-        return ClientResponse::Unauthorized(
-            "Your user is not allowed to execute this operation.".to_string(),
-        );
+    if let Err(response) = require_super_admin(&admin, "create an admin user") {
+        return response;
     }
 
-    let account: AdminAccountActiveModel = credentials.into_inner().into();
+    let NewAdmin {
+        credentials,
+        assignment,
+    } = new_admin.into_inner();
+    let zones = match assignment.zones() {
+        Ok(zones) => zones,
+        Err(response) => return response,
+    };
+
+    let account = AdminAccountActiveModel::new(credentials, assignment.role, &zones);
     let result = account.insert(db.inner()).await;
     if let Err(e) = result {
         match e {
@@ -200,23 +616,1179 @@ async fn new_admin(
     ClientResponse::Ok(format!("Account {} created.", result.unwrap().user))
 }
 
-#[derive(Template)]
-#[template(path = "command_download.txt")]
-pub struct CommandDownload {
-    username: String,
+fn require_super_admin(admin: &AdminAccount, action: &str) -> Result<(), ClientResponse> {
+    if admin.is_super_admin() {
+        return Ok(());
+    }
+
+    ::log::warn!(
+        "The user {} attempted to {}, but is not allowed.",
+        admin.get_user(),
+        action
+    );
+    Err(ClientResponse::Unauthorized(
+        "Your user is not allowed to execute this operation.".to_string(),
+    ))
+}
+
+/// The admin account can't be deleted, disabled or demoted, it would lock everyone out of the admin routes
+fn protect_super_admin(user: &str) -> Result<(), ClientResponse> {
+    if user == SUPER_ADMIN {
+        return Err(ClientResponse::NotAcceptable(String::from(
+            "The admin account can't be deleted, disabled, demoted or have its password reset.",
+        )));
+    }
+
+    Ok(())
+}
+
+fn not_allowed(admin: &AdminAccount, action: &str, domain: &str) -> ClientResponse {
+    ::log::warn!(
+        "The admin \"{}\" attempted to {} {}, but is not allowed.",
+        admin.get_user(),
+        action,
+        domain
+    );
+    ClientResponse::Unauthorized("Your user is not allowed to execute this operation.".to_string())
+}
+
+/// What an admin wants to do with the account of a domain
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Manage,
+}
+
+/// Checks the role and the zones of the admin against the domain and the creator of its account
+async fn authorize(
+    admin: &AdminAccount,
+    domain: &str,
+    access: Access,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    let created_by = account::created_by(domain, db).await.map_err(|e| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    })?;
+
+    let allowed = match access {
+        Access::Read => admin.can_read(domain, created_by.as_deref()),
+        Access::Manage => admin.can_manage(domain, created_by.as_deref()),
+    };
+
+    if allowed {
+        Ok(())
+    } else if access == Access::Read {
+        Err(not_allowed(admin, "read", domain))
+    } else {
+        Err(not_allowed(admin, "manage", domain))
+    }
+}
+
+fn admin_changed(result: Result<bool, DbErr>) -> Result<(), ClientResponse> {
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ClientResponse::NotFound(String::from(
+            "No admin exists with this username.",
+        ))),
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            Err(ClientResponse::InternalServerError(String::new()))
+        }
+    }
+}
+
+#[get("/admin/list")]
+async fn list_admins(
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<Vec<AdminSummary>>, ClientResponse> {
+    require_super_admin(&admin, "list the admin users")?;
+
+    AdminAccount::list(db.inner()).await.map(Json).map_err(|e| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    })
+}
+
+#[delete("/admin/<user>")]
+async fn delete_admin(user: &str, admin: AdminAccount, db: &State<DbConn>) -> ClientResponse {
+    if let Err(response) =
+        require_super_admin(&admin, "delete an admin user").and_then(|()| protect_super_admin(user))
+    {
+        return response;
+    }
+
+    if let Err(response) = admin_changed(AdminAccount::delete(user, db.inner()).await) {
+        return response;
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" deleted the admin {}",
+        admin.get_user(),
+        user
+    );
+    ClientResponse::Ok(format!("Account {user} deleted."))
+}
+
+/// Resets the password of another admin, and returns the new random password
+#[post("/admin/<user>/password")]
+async fn reset_admin_password(
+    user: &str,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+    rng: &State<Mutex<StdRng>>,
+) -> ClientResponse {
+    if let Err(response) = require_super_admin(&admin, "reset the password of an admin user") {
+        return response;
+    }
+    if let Err(response) = protect_super_admin(user) {
+        return response;
+    }
+
+    let (password, password_hash) = generate_random_password(rng.inner());
+    if let Err(response) =
+        admin_changed(AdminAccount::set_password_hash(user, &password_hash, db.inner()).await)
+    {
+        return response;
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" reset the password of the admin {}",
+        admin.get_user(),
+        user
+    );
+    ClientResponse::Ok(password)
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct NewPassword {
     password: String,
 }
 
-impl CommandDownload {
-    pub fn new(username: String, password: String) -> Self {
-        Self { username, password }
+/// Changes the password of the admin making the request
+#[post("/admin/password", data = "<new_password>")]
+async fn change_own_password(
+    new_password: Json<NewPassword>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    if admin.is_limited_token() {
+        return not_allowed(&admin, "change the password of", admin.get_user());
     }
 
-    fn url() -> String {
-        let mut url: String = std::env::var("FT_DDNS_BASE_URL").unwrap_or_default();
-        url.push_str("/ft-ddns.sh");
+    if new_password.password.is_empty() {
+        return ClientResponse::BadRequest(String::from("The password can't be empty."));
+    }
 
-        url
+    if let Err(response) = admin_changed(
+        AdminAccount::set_password(admin.get_user(), &new_password.password, db.inner()).await,
+    ) {
+        return response;
+    }
+
+    ::log::warn!("The admin \"{}\" changed their password", admin.get_user());
+    ClientResponse::Ok(String::from("Password changed."))
+}
+
+async fn set_admin_disabled(
+    user: &str,
+    disabled: bool,
+    admin: &AdminAccount,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    require_super_admin(admin, "disable or enable an admin user")?;
+    protect_super_admin(user)?;
+    admin_changed(AdminAccount::set_disabled(user, disabled, db).await)?;
+
+    ::log::warn!(
+        "The admin \"{}\" {} the admin {}",
+        admin.get_user(),
+        if disabled { "disabled" } else { "enabled" },
+        user
+    );
+    Ok(())
+}
+
+#[post("/admin/<user>/disable")]
+async fn disable_admin(user: &str, admin: AdminAccount, db: &State<DbConn>) -> ClientResponse {
+    match set_admin_disabled(user, true, &admin, db.inner()).await {
+        Ok(()) => ClientResponse::Ok(format!("Account {user} disabled.")),
+        Err(response) => response,
+    }
+}
+
+#[post("/admin/<user>/enable")]
+async fn enable_admin(user: &str, admin: AdminAccount, db: &State<DbConn>) -> ClientResponse {
+    match set_admin_disabled(user, false, &admin, db.inner()).await {
+        Ok(()) => ClientResponse::Ok(format!("Account {user} enabled.")),
+        Err(response) => response,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct NewToken {
+    name: String,
+    #[serde(default)]
+    read_only: bool,
+    /// RFC 3339 date after which the token is refused
+    expires_at: Option<String>,
+    /// FQDN suffixes the token is limited to, none means the zones of the admin
+    #[serde(default)]
+    zones: Vec<String>,
+}
+
+/// Mints an API token for the admin making the request
+///
+/// The token is only returned in this response, just its hash is stored.
+#[post("/admin/tokens", data = "<new_token>")]
+async fn mint_token(
+    new_token: Json<NewToken>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+    rng: &State<Mutex<StdRng>>,
+) -> Result<Json<MintedToken>, ClientResponse> {
+    if admin.is_limited_token() {
+        return Err(not_allowed(&admin, "mint a token for", admin.get_user()));
+    }
+
+    let NewToken {
+        name,
+        read_only,
+        expires_at,
+        zones,
+    } = new_token.into_inner();
+    if name.trim().is_empty() {
+        return Err(ClientResponse::BadRequest(String::from(
+            "The token must have a name.",
+        )));
+    }
+
+    let expires_at = match expires_at
+        .as_deref()
+        .map(chrono::DateTime::parse_from_rfc3339)
+    {
+        None => None,
+        Some(Ok(date)) if date > chrono::Utc::now() => Some(date.to_utc()),
+        Some(_) => {
+            return Err(ClientResponse::BadRequest(String::from(
+                "The expires_at date must be an RFC 3339 date in the future.",
+            )))
+        }
+    };
+
+    let zones = parse_zones(&zones)?;
+
+    let minted = admin_token::mint(
+        admin.get_user(),
+        name.trim().to_string(),
+        read_only,
+        expires_at,
+        &zones,
+        rng.inner(),
+        db.inner(),
+    )
+    .await
+    .map_err(|e| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    })?;
+
+    ::log::warn!(
+        "The admin \"{}\" minted the API token {} \"{}\"",
+        admin.get_user(),
+        minted.model.id,
+        minted.model.name
+    );
+    Ok(Json(minted))
+}
+
+/// Lists the API tokens of the admin making the request, or of another admin for the superadmins
+#[get("/admin/tokens?<user>")]
+async fn list_tokens(
+    user: Option<&str>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<Vec<admin_token::Model>>, ClientResponse> {
+    let user = match user {
+        Some(user) if user != admin.get_user() => {
+            require_super_admin(&admin, "list the tokens of an admin user")?;
+            user
+        }
+        _ => admin.get_user(),
+    };
+
+    admin_token::find_by_user(user, db.inner())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        })
+}
+
+/// Revokes an API token of the admin making the request, or of any admin for the superadmins
+#[delete("/admin/tokens/<id>")]
+async fn revoke_token(id: i64, admin: AdminAccount, db: &State<DbConn>) -> ClientResponse {
+    if admin.is_limited_token() {
+        return not_allowed(&admin, "revoke a token of", admin.get_user());
+    }
+
+    let user = (!admin.is_super_admin()).then(|| admin.get_user());
+    match admin_token::revoke(id, user, db.inner()).await {
+        Ok(true) => {
+            ::log::warn!(
+                "The admin \"{}\" revoked the API token {}",
+                admin.get_user(),
+                id
+            );
+            ClientResponse::Ok(String::from("Token revoked."))
+        }
+        Ok(false) => ClientResponse::NotFound(String::from("No token exists with this ID.")),
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        }
+    }
+}
+
+/// Replaces the role and the zones of an admin
+#[post("/admin/<user>/role", data = "<assignment>")]
+async fn set_admin_role(
+    user: &str,
+    assignment: Json<RoleAssignment>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let zones = match require_super_admin(&admin, "change the role of an admin user")
+        .and_then(|()| match assignment.role {
+            AdminRole::SuperAdmin => Ok(()),
+            _ => protect_super_admin(user),
+        })
+        .and_then(|()| assignment.zones())
+    {
+        Ok(zones) => zones,
+        Err(response) => return response,
+    };
+
+    if let Err(response) =
+        admin_changed(AdminAccount::set_role(user, assignment.role, &zones, db.inner()).await)
+    {
+        return response;
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" changed the role of the admin {} to {:?} in the zones {:?}",
+        admin.get_user(),
+        user,
+        assignment.role,
+        assignment.zones
+    );
+    ClientResponse::Ok(format!("Role of {user} changed."))
+}
+
+/// The zones of the DNS backend, limited to the ones sharing domains with the zones of the admin
+#[get("/zones")]
+fn list_zones(admin: AdminAccount, dns: &State<Dns>) -> Json<Vec<String>> {
+    Json(
+        dns.zones()
+            .iter()
+            .filter(|zone| admin.overlaps_zone(zone))
+            .map(ToString::to_string)
+            .collect(),
+    )
+}
+
+/// The addresses a domain currently points to on the DNS backend
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RecordLookup {
+    domain: String,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+}
+
+#[get("/dns/<domain>")]
+async fn lookup_records(
+    domain: &str,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    db: &State<DbConn>,
+) -> Result<Json<RecordLookup>, ClientResponse> {
+    authorize(&admin, domain.trim(), Access::Read, db.inner()).await?;
+
+    let domain = FQDN::from_str(domain.trim()).map_err(|_| {
+        ClientResponse::BadRequest(String::from("The domain name provided is invalid."))
+    })?;
+
+    let addresses = dns.lookup_address_records(&domain).await?;
+
+    Ok(Json(RecordLookup {
+        domain: domain.to_string(),
+        ipv4: addresses.ipv4,
+        ipv6: addresses.ipv6,
+    }))
+}
+
+/// Default number of entries per page in the listings
+const DEFAULT_PAGE_SIZE: u64 = 50;
+/// Maximum number of entries per page in the listings
+const MAX_PAGE_SIZE: u64 = 500;
+
+#[get("/accounts?<page>&<per_page>&<filter..>")]
+async fn list_accounts(
+    page: Option<u64>,
+    per_page: Option<u64>,
+    filter: AccountFilter,
+    admin: AdminAccount,
+    cache: &State<IpCache>,
+    db: &State<DbConn>,
+) -> Result<Json<AccountPage>, ClientResponse> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    account::list(&filter, &admin, page, per_page, cache.inner(), db.inner())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        })
+}
+
+#[get("/history/<domain>?<page>&<per_page>")]
+async fn domain_history(
+    domain: &str,
+    page: Option<u64>,
+    per_page: Option<u64>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<HistoryPage>, ClientResponse> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    authorize(&admin, domain.trim(), Access::Read, db.inner()).await?;
+
+    ::log::info!(
+        "The admin \"{}\" requested the update history of {}",
+        admin.get_user(),
+        domain.trim()
+    );
+
+    history::find_by_domain(domain.trim(), page, per_page, db.inner())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        })
+}
+
+/// What to do with the address records of a domain when its account is disabled
+#[derive(FromFormField, Clone, Copy, Default, PartialEq, Eq)]
+enum RecordAction {
+    /// Leave the records as they are
+    #[default]
+    Keep,
+    /// Delete the A and AAAA records
+    Remove,
+    /// Point the records to the parking addresses
+    Park,
+}
+
+/// The addresses a parked domain points to, managed in Rocket's state
+struct Parking {
+    ipv4: Ipv4Addr,
+    ipv6: Ipv6Addr,
+}
+
+impl Parking {
+    /// Reads `PARKING_IPV4` and `PARKING_IPV6`, defaulting to the unspecified addresses
+    fn from_env() -> Self {
+        Self {
+            ipv4: env::var("PARKING_IPV4")
+                .ok()
+                .map(|v| v.parse().expect("PARKING_IPV4 must be an IPv4 address."))
+                .unwrap_or(Ipv4Addr::UNSPECIFIED),
+            ipv6: env::var("PARKING_IPV6")
+                .ok()
+                .map(|v| v.parse().expect("PARKING_IPV6 must be an IPv6 address."))
+                .unwrap_or(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+
+    /// The parking addresses of the families the domain currently has a record for
+    fn addresses(&self, current: Addresses) -> Addresses {
+        Addresses {
+            ipv4: current.ipv4.map(|_| self.ipv4),
+            ipv6: current.ipv6.map(|_| self.ipv6),
+        }
+    }
+}
+
+async fn set_account_disabled(
+    domain: &str,
+    disabled: bool,
+    admin: &AdminAccount,
+    db: &DbConn,
+) -> Result<(), ClientResponse> {
+    match account::set_disabled(domain, disabled, db).await {
+        Ok(true) => {
+            ::log::warn!(
+                "The admin \"{}\" {} the account of the domain {}",
+                admin.get_user(),
+                if disabled { "disabled" } else { "enabled" },
+                domain
+            );
+            Ok(())
+        }
+        Ok(false) => Err(ClientResponse::NotFound(String::from(
+            "No account exists for this domain.",
+        ))),
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            Err(ClientResponse::InternalServerError(String::new()))
+        }
+    }
+}
+
+/// Removes or parks the records of the domain, returning whether they were changed
+#[allow(clippy::too_many_arguments)]
+async fn change_records(
+    domain: &str,
+    action: RecordAction,
+    admin: &AdminAccount,
+    dns: &Dns,
+    cache: &IpCache,
+    ttls: &TtlPolicy,
+    parking: &Parking,
+    db: &DbConn,
+) -> Result<bool, ClientResponse> {
+    let Ok(fqdn) = FQDN::from_str(domain) else {
+        return Ok(false);
+    };
+
+    let result = match action {
+        RecordAction::Keep => return Ok(false),
+        RecordAction::Remove => {
+            dns.delete_address_records(&fqdn).await?;
+            cache.remove(domain, db).await
+        }
+        RecordAction::Park => {
+            let parked = parking.addresses(dns.lookup_address_records(&fqdn).await?);
+            if parked.is_empty() {
+                return Ok(false);
+            }
+
+            let ttl = record_ttl(&fqdn, dns, ttls, db).await?;
+            dns.upsert_address_records(&fqdn, parked, ttl).await?;
+            cache.store(domain, parked, db).await
+        }
+    };
+
+    if let Err(e) = result {
+        ::log::error!("Couldn't update the last known IP of {domain}: {e}");
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" {} the records of the domain {}",
+        admin.get_user(),
+        if action == RecordAction::Park {
+            "parked"
+        } else {
+            "removed"
+        },
+        domain
+    );
+    Ok(true)
+}
+
+/// The TTL of the records of the name, from the account able to update it or from its zone
+async fn record_ttl(
+    name: &FQDN,
+    dns: &Dns,
+    ttls: &TtlPolicy,
+    db: &DbConn,
+) -> Result<u32, ClientResponse> {
+    let db_error = |e: DbErr| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    };
+
+    let account_ttl = match account::owner_of(&name.to_string(), db)
+        .await
+        .map_err(db_error)?
+    {
+        Some(owner) => account::ttl(&owner, db).await.map_err(db_error)?,
+        None => None,
+    };
+    let zone = dns.zone(name).ok_or_else(|| {
+        ClientResponse::NotAcceptable(String::from(
+            "The domain requested is not in any hosted zone that is enabled for dynamic DNS.",
+        ))
+    })?;
+
+    Ok(ttls.resolve(account_ttl, &zone))
+}
+
+/// The names holding records for the patterns of an account
+///
+/// The names under a subtree are the ones updated since the program keeps their last known IP.
+fn record_names(patterns: &[HostnamePattern], cache: &IpCache) -> Vec<String> {
+    patterns
+        .iter()
+        .flat_map(|pattern| match pattern {
+            HostnamePattern::Exact(hostname) => vec![hostname.clone()],
+            HostnamePattern::Subtree(_) => cache.domains_matching(pattern),
+        })
+        .map(|hostname| hostname.to_string())
+        .collect()
+}
+
+async fn forget_addresses(names: &[String], cache: &IpCache, db: &DbConn) {
+    for name in names {
+        if let Err(e) = cache.remove(name, db).await {
+            ::log::error!("Couldn't remove the last known IP of {name}: {e}");
+        }
+    }
+}
+
+/// The names holding records for every hostname and subtree of the account
+async fn account_record_names(
+    domain: &str,
+    cache: &IpCache,
+    db: &DbConn,
+) -> Result<Vec<String>, ClientResponse> {
+    account::hostnames(domain, db)
+        .await
+        .map(|patterns| record_names(&patterns, cache))
+        .map_err(|e| {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            ClientResponse::InternalServerError(String::new())
+        })
+}
+
+/// Removes or parks the records of every name of the account, returning whether any was changed
+#[allow(clippy::too_many_arguments)]
+async fn change_account_records(
+    domain: &str,
+    action: RecordAction,
+    admin: &AdminAccount,
+    dns: &Dns,
+    cache: &IpCache,
+    ttls: &TtlPolicy,
+    parking: &Parking,
+    db: &DbConn,
+) -> Result<bool, ClientResponse> {
+    let mut changed = false;
+    for name in account_record_names(domain, cache, db).await? {
+        changed |= change_records(&name, action, admin, dns, cache, ttls, parking, db).await?;
+    }
+
+    Ok(changed)
+}
+
+/// Disables the account of the domain, and removes or parks its records if requested
+///
+/// The account stays disabled when the change of the records fails.
+#[post("/account/<domain>/disable?<records>")]
+#[allow(clippy::too_many_arguments)]
+async fn disable_account(
+    domain: &str,
+    records: Option<RecordAction>,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    parking: &State<Parking>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    if let Err(response) = set_account_disabled(domain, true, &admin, db.inner()).await {
+        return response;
+    }
+
+    let action = records.unwrap_or_default();
+    match change_account_records(domain, action, &admin, dns, cache, ttls, parking, db).await {
+        Ok(true) => ClientResponse::Ok(String::from("Account disabled and its records changed.")),
+        Ok(false) => ClientResponse::Ok(String::from("Account disabled.")),
+        Err(response) => response,
+    }
+}
+
+/// Enables the account of the domain, its records are updated at the next request of the device
+#[post("/account/<domain>/enable")]
+async fn enable_account(domain: &str, admin: AdminAccount, db: &State<DbConn>) -> ClientResponse {
+    if let Err(response) = authorize(&admin, domain.trim(), Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match set_account_disabled(domain.trim(), false, &admin, db.inner()).await {
+        Ok(()) => ClientResponse::Ok(String::from("Account enabled.")),
+        Err(response) => response,
+    }
+}
+
+/// Deletes the account of the domain, after removing or parking its records if requested
+///
+/// The account is kept when the change of the records fails, so the deletion can be retried.
+#[delete("/account/<domain>?<records>")]
+#[allow(clippy::too_many_arguments)]
+async fn delete_account(
+    domain: &str,
+    records: Option<RecordAction>,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    parking: &State<Parking>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match account::exists(domain, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from("No account exists for this domain."))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    let names = match account_record_names(domain, cache, db).await {
+        Ok(names) => names,
+        Err(response) => return response,
+    };
+
+    let action = records.unwrap_or_default();
+    for name in &names {
+        if let Err(response) =
+            change_records(name, action, &admin, dns, cache, ttls, parking, db).await
+        {
+            return response;
+        }
+    }
+
+    if let Err(response) = acme::clear_account(domain, dns, ttls, db).await {
+        return response;
+    }
+
+    if let Err(e) = account::delete(domain, db.inner()).await {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        return ClientResponse::InternalServerError(String::new());
+    }
+
+    if action == RecordAction::Keep {
+        forget_addresses(&names, cache, db).await;
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" deleted the account of the domain {}",
+        admin.get_user(),
+        domain
+    );
+    ClientResponse::Ok(String::from("Account deleted."))
+}
+
+/// Every hostname updated by the account of the domain, starting with the domain itself
+#[get("/account/<domain>/hostnames")]
+async fn list_hostnames(
+    domain: &str,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<Vec<String>>, ClientResponse> {
+    let domain = domain.trim();
+    authorize(&admin, domain, Access::Read, db.inner()).await?;
+
+    let db_error = |e: DbErr| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    };
+
+    if !account::exists(domain, db.inner())
+        .await
+        .map_err(db_error)?
+    {
+        return Err(ClientResponse::NotFound(String::from(
+            "No account exists for this domain.",
+        )));
+    }
+
+    account::hostnames(domain, db.inner())
+        .await
+        .map(|hostnames| Json(hostnames.iter().map(ToString::to_string).collect()))
+        .map_err(db_error)
+}
+
+/// Adds a hostname to the account of the domain, updated along with the domain
+#[post("/account/<domain>/hostnames/<hostname>")]
+async fn add_hostname(
+    domain: &str,
+    hostname: &str,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    let Ok(pattern) = HostnamePattern::from_str(hostname.trim()) else {
+        return ClientResponse::BadRequest(String::from("The hostname provided is invalid."));
+    };
+    let hostname = pattern.to_string();
+
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    if !dns.domain_included(pattern.fqdn()) {
+        return ClientResponse::NotAcceptable(String::from(
+            "The hostname provided is not available for dynamic DNS.",
+        ));
+    }
+
+    if !admin.can_create(&hostname) {
+        return not_allowed(&admin, "add the hostname", &hostname);
+    }
+
+    match account::exists(domain, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from("No account exists for this domain."))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    match account::in_use(&hostname, db.inner()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return ClientResponse::Conflict(String::from(
+                "The hostname provided is already in use.",
+            ))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    if let Err(e) = hostname::add(domain, &hostname, db.inner()).await {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        return ClientResponse::InternalServerError(String::new());
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" added the hostname {} to the domain {}",
+        admin.get_user(),
+        hostname,
+        domain
+    );
+    ClientResponse::Ok(String::from("Hostname added."))
+}
+
+/// Removes a hostname from the account of the domain, after removing or parking its records if requested
+#[delete("/account/<domain>/hostnames/<hostname>?<records>")]
+#[allow(clippy::too_many_arguments)]
+async fn remove_hostname(
+    domain: &str,
+    hostname: &str,
+    records: Option<RecordAction>,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    parking: &State<Parking>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    let Ok(pattern) = HostnamePattern::from_str(hostname.trim()) else {
+        return ClientResponse::BadRequest(String::from("The hostname provided is invalid."));
+    };
+    let hostname = pattern.to_string();
+
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match hostname::owner(&hostname, db.inner()).await {
+        Ok(Some(owner)) if owner == domain => {}
+        Ok(_) => {
+            return ClientResponse::NotFound(String::from(
+                "The account of the domain doesn't have this hostname.",
+            ))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    let names = record_names(std::slice::from_ref(&pattern), cache);
+    let action = records.unwrap_or_default();
+    for name in &names {
+        if let Err(response) =
+            change_records(name, action, &admin, dns, cache, ttls, parking, db).await
+        {
+            return response;
+        }
+    }
+
+    if let Err(e) = hostname::remove(domain, &hostname, db.inner()).await {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        return ClientResponse::InternalServerError(String::new());
+    }
+
+    if action == RecordAction::Keep {
+        forget_addresses(&names, cache, db).await;
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" removed the hostname {} from the domain {}",
+        admin.get_user(),
+        hostname,
+        domain
+    );
+    ClientResponse::Ok(String::from("Hostname removed."))
+}
+
+/// Sets the TTL of the account of the domain, or goes back to the default of its zone without a TTL
+///
+/// The records are written with the new TTL at their next update.
+#[post("/account/<domain>/ttl?<ttl>")]
+async fn set_account_ttl(
+    domain: &str,
+    ttl: Option<u32>,
+    admin: AdminAccount,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    if let Some(Err(response)) = ttl.map(|ttl| ttls.check(ttl)) {
+        return response;
+    }
+
+    match account::set_ttl(domain, ttl, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from("No account exists for this domain."))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    match account_record_names(domain, cache, db).await {
+        Ok(names) => cache.invalidate(&names),
+        Err(response) => return response,
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" set the TTL of the account of the domain {} to {:?}",
+        admin.get_user(),
+        domain,
+        ttl
+    );
+    ClientResponse::Ok(String::from("TTL changed."))
+}
+
+/// The addresses the account of the domain can set other than the IP of its client
+#[get("/account/<domain>/ip-policy")]
+async fn get_ip_policy(
+    domain: &str,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> Result<Json<IpPolicy>, ClientResponse> {
+    let domain = domain.trim();
+    authorize(&admin, domain, Access::Read, db.inner()).await?;
+
+    let db_error = |e: DbErr| {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        ClientResponse::InternalServerError(String::new())
+    };
+
+    if !account::exists(domain, db.inner())
+        .await
+        .map_err(db_error)?
+    {
+        return Err(ClientResponse::NotFound(String::from(
+            "No account exists for this domain.",
+        )));
+    }
+
+    ip_policy::find(domain, db.inner())
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+/// Replaces the IP policy of the account of the domain, applied from its next update
+#[post("/account/<domain>/ip-policy", data = "<policy>")]
+async fn set_ip_policy(
+    domain: &str,
+    policy: Json<IpPolicy>,
+    admin: AdminAccount,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let domain = domain.trim();
+    if let Err(response) = authorize(&admin, domain, Access::Manage, db.inner()).await {
+        return response;
+    }
+
+    match (policy.mode, policy.networks.is_empty()) {
+        (IpOverride::Networks, true) => {
+            return ClientResponse::BadRequest(String::from(
+                "The networks mode requires at least one network.",
+            ))
+        }
+        (IpOverride::Observed | IpOverride::Any, false) => {
+            return ClientResponse::BadRequest(String::from(
+                "The networks are only used by the networks mode.",
+            ))
+        }
+        _ => {}
+    }
+
+    match account::exists(domain, db.inner()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ClientResponse::NotFound(String::from("No account exists for this domain."))
+        }
+        Err(e) => {
+            ::log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    if let Err(e) = ip_policy::set(domain, &policy, db.inner()).await {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        return ClientResponse::InternalServerError(String::new());
+    }
+
+    ::log::warn!(
+        "The admin \"{}\" set the IP policy of the account of the domain {} to {:?}",
+        admin.get_user(),
+        domain,
+        policy.into_inner()
+    );
+    ClientResponse::Ok(String::from("IP policy changed."))
+}
+
+/// The default TTL of a zone
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ZoneTtl {
+    zone: String,
+    ttl: u32,
+    /// Whether the TTL was set for the zone, rather than being the global default
+    custom: bool,
+}
+
+/// The default TTL of the zones sharing domains with the zones of the admin
+#[get("/zones/ttl")]
+fn list_zone_ttls(
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+) -> Json<Vec<ZoneTtl>> {
+    Json(
+        dns.zones()
+            .iter()
+            .filter(|zone| admin.overlaps_zone(zone))
+            .map(|zone| ZoneTtl {
+                zone: zone.to_string(),
+                ttl: ttls.resolve(None, zone),
+                custom: ttls.zone_ttl(zone).is_some(),
+            })
+            .collect(),
+    )
+}
+
+/// Sets the default TTL of the zone, or goes back to the global default without a TTL
+///
+/// The records of the accounts without a TTL of their own are written with it at their next update.
+#[post("/zones/<zone>/ttl?<ttl>")]
+async fn set_zone_ttl(
+    zone: &str,
+    ttl: Option<u32>,
+    admin: AdminAccount,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let Some(zone) = FQDN::from_str(zone.trim())
+        .ok()
+        .filter(|zone| dns.zones().contains(zone))
+    else {
+        return ClientResponse::NotFound(String::from(
+            "No zone enabled for dynamic DNS has this name.",
+        ));
+    };
+
+    if !admin.can_create(&zone.to_string()) {
+        return not_allowed(&admin, "set the TTL of the zone", &zone.to_string());
+    }
+
+    if let Some(Err(response)) = ttl.map(|ttl| ttls.check(ttl)) {
+        return response;
+    }
+
+    if let Err(e) = ttls.set_zone_ttl(&zone, ttl, db.inner()).await {
+        ::log::error!("An error occured communicating with the database: {}", e);
+        return ClientResponse::InternalServerError(String::new());
+    }
+
+    let mut names = record_names(&[HostnamePattern::Subtree(zone.clone())], cache);
+    names.push(zone.to_string());
+    cache.invalidate(&names);
+
+    ::log::warn!(
+        "The admin \"{}\" set the default TTL of the zone {} to {:?}",
+        admin.get_user(),
+        zone,
+        ttl
+    );
+    ClientResponse::Ok(String::from("TTL changed."))
+}
+
+#[derive(Template)]
+#[template(path = "command_download.txt")]
+pub struct CommandDownload {
+    username: String,
+    password: String,
+}
+
+impl CommandDownload {
+    pub fn new(username: String, password: String) -> Self {
+        Self { username, password }
+    }
+
+    fn url() -> String {
+        let mut url: String = std::env::var("FT_DDNS_BASE_URL").unwrap_or_default();
+        url.push_str("/ft-ddns.sh");
+
+        url
     }
 }
 
@@ -225,3 +1797,63 @@ impl From<Credentials> for CommandDownload {
         Self::new(value.username, value.password)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::PKey,
+        rsa::Rsa,
+    };
+
+    use super::*;
+
+    fn upload(pem: &[u8]) -> Result<PublicKey, String> {
+        decode_public_key(pem, "operator", &KeyPolicy::default()).map_err(|response| match response
+        {
+            ClientResponse::NotAcceptable(reason) => reason,
+            _ => panic!("The key should be refused with 406 Not Acceptable"),
+        })
+    }
+
+    fn ec_pem(nid: Nid) -> Vec<u8> {
+        let group = EcGroup::from_curve_name(nid).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        key.public_key_to_pem().unwrap()
+    }
+
+    #[test]
+    fn accepts_strong_keys() {
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+
+        assert!(upload(&rsa.public_key_to_pem().unwrap()).is_ok());
+        assert!(upload(&ec_pem(Nid::X9_62_PRIME256V1)).is_ok());
+    }
+
+    #[test]
+    fn refuses_short_rsa_keys() {
+        let rsa = PKey::from_rsa(Rsa::generate(1024).unwrap()).unwrap();
+
+        assert_eq!(
+            upload(&rsa.public_key_to_pem().unwrap()).err().as_deref(),
+            Some("RSA public keys must have at least 2048 bits.")
+        );
+    }
+
+    #[test]
+    fn refuses_secp256k1_keys() {
+        assert_eq!(
+            upload(&ec_pem(Nid::SECP256K1)).err().as_deref(),
+            Some("ECDSA public keys must use the P-256, P-384 or P-521 curve.")
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_keys() {
+        assert_eq!(
+            upload(b"ssh-ed25519 AAAA").err().as_deref(),
+            Some("The public key uploaded is not a valid PEM or OpenSSH key.")
+        );
+    }
+}
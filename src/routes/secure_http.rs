@@ -1,9 +1,19 @@
 use askama::Template;
-use fqdn::fqdn;
-use rocket::{fairing::AdHoc, get, post, routes, serde::json::Json, State};
+use fqdn::FQDN;
+use rocket::{delete, fairing::AdHoc, get, http::Status, post, routes, serde::json::Json, State};
+use sea_orm::DbConn;
 
 use crate::{
-    account::PasswordAccount, client_response::ClientResponse, ip::IP, route53::Route53,
+    account::{self, ip_policy, AuthError, HostnamePattern, PasswordAccount},
+    acme::{self, AcmeDnsResponse, AcmeDnsUpdate, HttpReqChallenge},
+    client_response::ClientResponse,
+    dns::Dns,
+    dyndns::{parse_hostnames, parse_myip, DynDnsCode, DynDnsResponse, MAX_HOSTNAMES},
+    history::{AuthMethod, RequestOrigin, UpdateAttempt},
+    ip::{Addresses, IP},
+    ip_cache::IpCache,
+    ttl::TtlPolicy,
+    update::{update_hostnames, UpdateOutcome},
     utils::Credentials,
 };
 
@@ -12,23 +22,322 @@ pub fn stage() -> AdHoc {
         Box::pin(async move {
             rocket.mount(
                 "/secure",
-                routes![set_record, shell_program, shell_program_empty],
+                routes![
+                    set_record,
+                    shell_program,
+                    shell_program_empty,
+                    present_challenge,
+                    cleanup_challenge,
+                    acme_dns_update,
+                    httpreq_present,
+                    httpreq_cleanup
+                ],
             )
         })
     })
 }
 
-#[get("/nic/update")]
-async fn set_record(a: PasswordAccount, ip: IP, route53: &State<Route53>) -> ClientResponse {
-    log::info!(
-        "Attempting to update DNS {} to {}",
-        a.get_domain(),
-        ip.get()
-    );
-    route53
-        .inner()
-        .upsert_a_resource_record(fqdn!(a.get_domain()), ip.get())
-        .await
+/// DynDNS2 compatible update
+///
+/// The `ipv4` and `ipv6` parameters are kept as aliases of `myip` and `myipv6`.
+#[get("/nic/update?<hostname>&<myip>&<myipv6>&<ipv4>&<ipv6>")]
+#[allow(clippy::too_many_arguments)]
+async fn set_record(
+    account: Result<PasswordAccount, AuthError>,
+    ip: IP,
+    origin: RequestOrigin,
+    hostname: Option<&str>,
+    myip: Option<&str>,
+    myipv6: Option<&str>,
+    ipv4: Option<&str>,
+    ipv6: Option<&str>,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> DynDnsResponse {
+    let a = match account {
+        Ok(a) => a,
+        Err(AuthError::Invalid) => {
+            log::warn!("{ip}: DynDNS update with invalid credentials");
+            return DynDnsResponse::single(DynDnsCode::BadAuth);
+        }
+        Err(AuthError::Disabled) => return DynDnsResponse::single(DynDnsCode::Abuse),
+    };
+
+    let mut addresses = parse_myip(myip.or(ipv4), myipv6.or(ipv6));
+    if addresses.is_empty() {
+        addresses = Addresses::from(ip.get());
+    }
+
+    match ip_policy::find(a.get_domain(), db.inner()).await {
+        Ok(policy) if policy.allows(&addresses, &ip) => {}
+        Ok(_) => {
+            log::warn!(
+                "{ip}: The IP policy of {} refused the update to {}",
+                a.get_domain(),
+                addresses
+            );
+            return DynDnsResponse::single(DynDnsCode::Abuse);
+        }
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            return DynDnsResponse::single(DynDnsCode::ServerError);
+        }
+    }
+
+    let owned = match account::hostnames(a.get_domain(), db.inner()).await {
+        Ok(owned) => owned,
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            return DynDnsResponse::single(DynDnsCode::ServerError);
+        }
+    };
+
+    let attempt = UpdateAttempt {
+        domain: a.get_domain(),
+        auth_method: AuthMethod::Password,
+        origin: &origin,
+    };
+
+    // Without hostnames, every hostname of the account is updated and a single code is returned
+    // The names under the subtrees of the account are only updated when requested
+    let Some(hostnames) = hostname.map(parse_hostnames).filter(|h| !h.is_empty()) else {
+        let exact: Vec<FQDN> = owned
+            .iter()
+            .filter_map(HostnamePattern::exact)
+            .cloned()
+            .collect();
+        if exact.is_empty() {
+            log::info!(
+                "{ip}: DynDNS update for the subtree account {} without hostname",
+                a.get_domain()
+            );
+            return DynDnsResponse::single(DynDnsCode::NoHost);
+        }
+
+        log::info!(
+            "Attempting to update DNS {} to {}",
+            a.get_domain(),
+            addresses
+        );
+        let outcomes = update_hostnames(
+            &attempt,
+            &exact,
+            addresses,
+            dns.inner(),
+            cache.inner(),
+            ttls.inner(),
+            db.inner(),
+        )
+        .await;
+        let outcome = UpdateOutcome::combine(outcomes.into_iter().map(|(_, o)| o));
+        return DynDnsResponse::single(DynDnsCode::from_outcome(&outcome, addresses));
+    };
+
+    if hostnames.len() > MAX_HOSTNAMES {
+        log::warn!(
+            "{ip}: DynDNS update for {} with {} hostnames",
+            a.get_domain(),
+            hostnames.len()
+        );
+        return DynDnsResponse::single(DynDnsCode::NumHost);
+    }
+
+    let mut requested: Vec<FQDN> = Vec::with_capacity(hostnames.len());
+    for hostname in hostnames.iter().flatten() {
+        if owned.iter().any(|p| p.matches(hostname)) && !requested.contains(hostname) {
+            requested.push(hostname.clone());
+        }
+    }
+
+    if !requested.is_empty() {
+        log::info!(
+            "Attempting to update DNS {:?} of {} to {}",
+            requested
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            a.get_domain(),
+            addresses
+        );
+    }
+    let outcomes = update_hostnames(
+        &attempt,
+        &requested,
+        addresses,
+        dns.inner(),
+        cache.inner(),
+        ttls.inner(),
+        db.inner(),
+    )
+    .await;
+
+    let mut codes: Vec<DynDnsCode> = Vec::with_capacity(hostnames.len());
+    for hostname in hostnames {
+        let code = match hostname {
+            Err(()) => DynDnsCode::NotFqdn,
+            Ok(h) => match outcomes.iter().find(|(o, _)| o == &h) {
+                Some((_, outcome)) => DynDnsCode::from_outcome(outcome, addresses),
+                None => {
+                    log::warn!(
+                        "{ip}: The account {} attempted to update {h}",
+                        a.get_domain()
+                    );
+                    let reason = format!("Hostname {h} not owned by the account");
+                    attempt.rejected(db.inner(), Some(addresses), &reason).await;
+                    DynDnsCode::NoHost
+                }
+            },
+        };
+        codes.push(code);
+    }
+
+    DynDnsResponse::new(codes)
+}
+
+/// Adds a value to the ACME challenge of a hostname of the account, its domain by default
+#[post("/acme/challenge?<hostname>&<value>")]
+async fn present_challenge(
+    a: PasswordAccount,
+    hostname: Option<&str>,
+    value: &str,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let result = match acme::owned_hostname(a.get_domain(), hostname, db).await {
+        Ok(hostname) => acme::present(a.get_domain(), &hostname, value, dns, ttls, db).await,
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge set.")),
+        Err(response) => response,
+    }
+}
+
+/// Removes a value from the ACME challenge of a hostname of the account, or every value without one
+#[delete("/acme/challenge?<hostname>&<value>")]
+async fn cleanup_challenge(
+    a: PasswordAccount,
+    hostname: Option<&str>,
+    value: Option<&str>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let result = match acme::owned_hostname(a.get_domain(), hostname, db).await {
+        Ok(hostname) => acme::cleanup(a.get_domain(), &hostname, value, dns, ttls, db).await,
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge cleared.")),
+        Err(response) => response,
+    }
+}
+
+/// acme-dns compatible update, authenticated with the `X-Api-User` and `X-Api-Key` headers
+///
+/// The subdomain is the hostname whose challenge is set, rather than an acme-dns subdomain.
+#[post("/update", data = "<update>")]
+async fn acme_dns_update(
+    account: Result<PasswordAccount, AuthError>,
+    update: Json<AcmeDnsUpdate>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> AcmeDnsResponse {
+    let Ok(a) = account else {
+        return AcmeDnsResponse::Error(Status::Unauthorized, "forbidden");
+    };
+
+    if !acme::is_valid_value(&update.txt) {
+        return AcmeDnsResponse::Error(Status::BadRequest, "bad_txt");
+    }
+
+    let hostname = match acme::owned_hostname(a.get_domain(), Some(&update.subdomain), db).await {
+        Ok(hostname) => hostname,
+        Err(response) => return response.into(),
+    };
+
+    match acme::present(a.get_domain(), &hostname, &update.txt, dns, ttls, db).await {
+        Ok(()) => AcmeDnsResponse::Updated(update.into_inner().txt),
+        Err(response) => response.into(),
+    }
+}
+
+/// The hostname of the challenge name of a lego `httpreq` request, when the account owns it
+async fn httpreq_hostname(
+    a: &PasswordAccount,
+    challenge: &HttpReqChallenge,
+    db: &DbConn,
+) -> Result<FQDN, ClientResponse> {
+    let Some(hostname) = acme::challenge_hostname(&challenge.fqdn) else {
+        log::warn!(
+            "The account {} attempted to set the challenge name {}",
+            a.get_domain(),
+            challenge.fqdn
+        );
+        return Err(ClientResponse::BadRequest(String::from(
+            "The fqdn must be an _acme-challenge name.",
+        )));
+    };
+
+    acme::owned_hostname(a.get_domain(), Some(hostname), db).await
+}
+
+/// lego `httpreq` compatible route, adding the value to the challenge
+#[post("/present", data = "<challenge>")]
+async fn httpreq_present(
+    a: PasswordAccount,
+    challenge: Json<HttpReqChallenge>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let result = match httpreq_hostname(&a, &challenge, db).await {
+        Ok(hostname) => {
+            acme::present(a.get_domain(), &hostname, &challenge.value, dns, ttls, db).await
+        }
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge set.")),
+        Err(response) => response,
+    }
+}
+
+/// lego `httpreq` compatible route, removing the value from the challenge
+#[post("/cleanup", data = "<challenge>")]
+async fn httpreq_cleanup(
+    a: PasswordAccount,
+    challenge: Json<HttpReqChallenge>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    let result = match httpreq_hostname(&a, &challenge, db).await {
+        Ok(hostname) => {
+            acme::cleanup(
+                a.get_domain(),
+                &hostname,
+                Some(&challenge.value),
+                dns,
+                ttls,
+                db,
+            )
+            .await
+        }
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge cleared.")),
+        Err(response) => response,
+    }
 }
 
 #[get("/ft-ddns.sh")]
@@ -1,28 +1,242 @@
-use fqdn::fqdn;
-use rocket::{fairing::AdHoc, get, routes, State};
+use fqdn::FQDN;
+use rocket::{delete, fairing::AdHoc, get, post, routes, State};
+use sea_orm::DbConn;
 
 use crate::{
-    account::{Account, SigningAccount},
+    account::{self, ip_policy, Account, HostnamePattern, SigningAccount},
+    acme,
     client_response::ClientResponse,
-    ip::IP,
-    route53::Route53,
+    dns::Dns,
+    dyndns::{parse_hostnames, MAX_HOSTNAMES},
+    history::{AuthMethod, RequestOrigin, UpdateAttempt},
+    ip::{Addresses, IP},
+    ip_cache::IpCache,
+    ttl::TtlPolicy,
+    update::{update_hostnames, UpdateOutcome},
 };
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Mount unsecure routes", |rocket| {
-        Box::pin(async move { rocket.mount("/unsecure", routes![set_record]) })
+        Box::pin(async move {
+            rocket.mount(
+                "/unsecure",
+                routes![set_record, present_challenge, cleanup_challenge],
+            )
+        })
     })
 }
 
-#[get("/nic/update")]
-async fn set_record(a: SigningAccount, ip: IP, route53: &State<Route53>) -> ClientResponse {
+/// The addresses the records receive
+///
+/// Only the addresses covered by the signature are set, the IP of the client for version 1.
+fn addresses_to_set(signed: Option<Addresses>, client: &IP) -> Addresses {
+    signed.unwrap_or_else(|| Addresses::from(client.get()))
+}
+
+/// Updates the hostnames of the account, or the requested ones
+///
+/// The names under the subtrees of the account are only updated when requested.
+#[get("/nic/update?<hostname>")]
+#[allow(clippy::too_many_arguments)]
+async fn set_record(
+    a: SigningAccount,
+    ip: IP,
+    origin: RequestOrigin,
+    hostname: Option<&str>,
+    dns: &State<Dns>,
+    cache: &State<IpCache>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    // The version 1 signatures don't cover the hostnames, which could be changed in transit
+    if hostname.is_some() && a.signed_addresses().is_none() {
+        log::warn!(
+            "{ip}: Update for {} with hostnames and a version 1 signature",
+            a.get_domain()
+        );
+        return ClientResponse::BadRequest(String::from(
+            "The hostname parameter requires a version 2 signature.",
+        ));
+    }
+
+    let addresses = addresses_to_set(a.signed_addresses(), &ip);
+
+    match ip_policy::find(a.get_domain(), db.inner()).await {
+        Ok(policy) if policy.allows(&addresses, &ip) => {}
+        Ok(_) => {
+            log::warn!(
+                "{ip}: The IP policy of {} refused the update to {}",
+                a.get_domain(),
+                addresses
+            );
+            return ClientResponse::NotAcceptable(String::from(
+                "The IP policy of the account doesn't allow these addresses.",
+            ));
+        }
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    }
+
+    let owned = match account::hostnames(a.get_domain(), db.inner()).await {
+        Ok(owned) => owned,
+        Err(e) => {
+            log::error!("An error occured communicating with the database: {}", e);
+            return ClientResponse::InternalServerError(String::new());
+        }
+    };
+
+    let hostnames: Vec<FQDN> = match hostname.map(parse_hostnames) {
+        Some(requested) if requested.len() > MAX_HOSTNAMES => {
+            log::warn!(
+                "{ip}: Update for {} with {} hostnames",
+                a.get_domain(),
+                requested.len()
+            );
+            return ClientResponse::BadRequest(String::from("Too many hostnames were requested."));
+        }
+        Some(requested) if !requested.is_empty() => {
+            let mut hostnames: Vec<FQDN> = Vec::with_capacity(requested.len());
+            for hostname in requested {
+                match hostname {
+                    Ok(h) if owned.iter().any(|p| p.matches(&h)) => hostnames.push(h),
+                    _ => {
+                        log::warn!(
+                            "{ip}: The account {} attempted to update {:?}",
+                            a.get_domain(),
+                            hostname
+                        );
+                        return ClientResponse::NotAcceptable(String::from(
+                            "A hostname requested is not owned by the account.",
+                        ));
+                    }
+                }
+            }
+            hostnames
+        }
+        _ => owned
+            .iter()
+            .filter_map(HostnamePattern::exact)
+            .cloned()
+            .collect(),
+    };
+
+    if hostnames.is_empty() {
+        return ClientResponse::BadRequest(String::from(
+            "The hostname parameter is required for a subtree account.",
+        ));
+    }
+
     log::info!(
         "Attempting to update DNS {} to {}",
         a.get_domain(),
-        ip.get()
+        addresses
     );
-    route53
-        .inner()
-        .upsert_a_resource_record(fqdn!(a.get_domain()), ip.get())
-        .await
+    let attempt = UpdateAttempt {
+        domain: a.get_domain(),
+        auth_method: AuthMethod::Signing,
+        origin: &origin,
+    };
+
+    let outcomes = update_hostnames(
+        &attempt,
+        &hostnames,
+        addresses,
+        dns.inner(),
+        cache.inner(),
+        ttls.inner(),
+        db.inner(),
+    )
+    .await;
+    UpdateOutcome::combine(outcomes.into_iter().map(|(_, o)| o)).into()
+}
+
+/// Adds a value to the ACME challenge of a hostname of the account, its domain by default
+#[post("/acme/challenge?<hostname>&<value>")]
+async fn present_challenge(
+    a: SigningAccount,
+    hostname: Option<&str>,
+    value: &str,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    // The version 1 signatures don't cover the route and the value
+    if a.signed_addresses().is_none() {
+        return ClientResponse::BadRequest(String::from(
+            "The ACME challenges require a version 2 signature.",
+        ));
+    }
+
+    let result = match acme::owned_hostname(a.get_domain(), hostname, db).await {
+        Ok(hostname) => acme::present(a.get_domain(), &hostname, value, dns, ttls, db).await,
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge set.")),
+        Err(response) => response,
+    }
+}
+
+/// Removes a value from the ACME challenge of a hostname of the account, or every value without one
+#[delete("/acme/challenge?<hostname>&<value>")]
+async fn cleanup_challenge(
+    a: SigningAccount,
+    hostname: Option<&str>,
+    value: Option<&str>,
+    dns: &State<Dns>,
+    ttls: &State<TtlPolicy>,
+    db: &State<DbConn>,
+) -> ClientResponse {
+    // The version 1 signatures don't cover the route and the value
+    if a.signed_addresses().is_none() {
+        return ClientResponse::BadRequest(String::from(
+            "The ACME challenges require a version 2 signature.",
+        ));
+    }
+
+    let result = match acme::owned_hostname(a.get_domain(), hostname, db).await {
+        Ok(hostname) => acme::cleanup(a.get_domain(), &hostname, value, dns, ttls, db).await,
+        Err(response) => Err(response),
+    };
+
+    match result {
+        Ok(()) => ClientResponse::Ok(String::from("Challenge cleared.")),
+        Err(response) => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::account::ip_policy::{IpOverride, IpPolicy};
+
+    use super::*;
+
+    #[test]
+    fn version_1_only_sets_the_client_ip() {
+        let observed = IpPolicy {
+            mode: IpOverride::Observed,
+            networks: Vec::new(),
+        };
+
+        for client in ["192.0.2.1", "::ffff:192.0.2.1", "2001:db8::1"] {
+            let client = IP::from_str(client).unwrap();
+            let addresses = addresses_to_set(None, &client);
+
+            assert_eq!(addresses, Addresses::from(client.get()));
+            assert!(observed.allows(&addresses, &client));
+        }
+    }
+
+    #[test]
+    fn version_2_sets_the_signed_addresses() {
+        let client = IP::from_str("192.0.2.1").unwrap();
+        let signed = Addresses::from_list("203.0.113.7,2001:db8::7").unwrap();
+
+        assert_eq!(addresses_to_set(Some(signed), &client), signed);
+    }
 }
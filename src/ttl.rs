@@ -0,0 +1,147 @@
+use std::{collections::HashMap, env, str::FromStr, sync::RwLock};
+
+use fqdn::FQDN;
+use rocket::fairing::AdHoc;
+use sea_orm::{entity::prelude::*, sea_query::OnConflict, Set};
+
+use crate::client_response::ClientResponse;
+
+/// Default time to live of the address records, in seconds
+const DEFAULT_TTL: u32 = 180;
+/// Default lowest TTL an account or a zone can be given
+const DEFAULT_MIN_TTL: u32 = 30;
+/// Default highest TTL an account or a zone can be given
+const DEFAULT_MAX_TTL: u32 = 86400;
+
+/// The default TTL of a zone, replacing the global default
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "zone_ttl")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub zone: String,
+    pub ttl: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// The TTL limits and the default TTL of the zones, managed in Rocket's state
+///
+/// The records of an account use its own TTL, then the default of their zone,
+/// then the global default.
+pub struct TtlPolicy {
+    min: u32,
+    max: u32,
+    default: u32,
+    zones: RwLock<HashMap<FQDN, u32>>,
+}
+
+impl TtlPolicy {
+    /// Accepts the TTL when it is within the configured limits
+    pub fn check(&self, ttl: u32) -> Result<u32, ClientResponse> {
+        if (self.min..=self.max).contains(&ttl) {
+            Ok(ttl)
+        } else {
+            Err(ClientResponse::BadRequest(format!(
+                "The TTL must be between {} and {} seconds.",
+                self.min, self.max
+            )))
+        }
+    }
+
+    /// The lowest TTL allowed
+    pub fn min(&self) -> u32 {
+        self.min
+    }
+
+    /// The default TTL set for the zone, `None` when it uses the global default
+    pub fn zone_ttl(&self, zone: &FQDN) -> Option<u32> {
+        self.zones.read().unwrap().get(zone).copied()
+    }
+
+    /// The TTL of the records of an account in the zone
+    ///
+    /// TTLs stored before the limits were changed are brought back within them.
+    pub fn resolve(&self, account_ttl: Option<u32>, zone: &FQDN) -> u32 {
+        account_ttl
+            .or_else(|| self.zone_ttl(zone))
+            .unwrap_or(self.default)
+            .clamp(self.min, self.max)
+    }
+
+    /// Sets the default TTL of the zone, or goes back to the global default
+    pub async fn set_zone_ttl(
+        &self,
+        zone: &FQDN,
+        ttl: Option<u32>,
+        db: &DbConn,
+    ) -> Result<(), DbErr> {
+        match ttl {
+            Some(ttl) => {
+                Entity::insert(ActiveModel {
+                    zone: Set(zone.to_string()),
+                    ttl: Set(ttl as i32),
+                })
+                .on_conflict(
+                    OnConflict::column(Column::Zone)
+                        .update_column(Column::Ttl)
+                        .to_owned(),
+                )
+                .exec(db)
+                .await?;
+                self.zones.write().unwrap().insert(zone.clone(), ttl);
+            }
+            None => {
+                Entity::delete_by_id(zone.to_string()).exec(db).await?;
+                self.zones.write().unwrap().remove(zone);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn env_ttl(name: &str, default: u32) -> u32 {
+    env::var(name)
+        .ok()
+        .map(|v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("{name} must be a number of seconds."))
+        })
+        .unwrap_or(default)
+}
+
+/// Loads the TTL limits from `TTL_MIN`, `TTL_MAX` and `TTL_DEFAULT`, and the default TTL of the zones
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Loading TTL policy", |rocket| {
+        Box::pin(async move {
+            let db = rocket
+                .state::<DbConn>()
+                .expect("Must mount the database before loading the TTL policy.");
+
+            let min = env_ttl("TTL_MIN", DEFAULT_MIN_TTL);
+            let max = env_ttl("TTL_MAX", DEFAULT_MAX_TTL);
+            let default = env_ttl("TTL_DEFAULT", DEFAULT_TTL);
+            if min > max || !(min..=max).contains(&default) {
+                panic!("TTL_DEFAULT must be between TTL_MIN and TTL_MAX.");
+            }
+
+            let zones: HashMap<FQDN, u32> = Entity::find()
+                .all(db)
+                .await
+                .expect("Couldn't load the default TTL of the zones from the database.")
+                .into_iter()
+                .filter_map(|m| Some((FQDN::from_str(&m.zone).ok()?, m.ttl as u32)))
+                .collect();
+
+            rocket.manage(TtlPolicy {
+                min,
+                max,
+                default,
+                zones: RwLock::new(zones),
+            })
+        })
+    })
+}
@@ -0,0 +1,113 @@
+use fqdn::FQDN;
+use sea_orm::DbConn;
+
+use crate::{
+    account, client_response::ClientResponse, dns::Dns, history::UpdateAttempt, ip::Addresses,
+    ip_cache::IpCache, ttl::TtlPolicy,
+};
+
+/// The outcome of a request to point a domain to new addresses
+pub enum UpdateOutcome {
+    /// The domain already had the addresses, no change was sent
+    Unchanged,
+    /// The change was accepted by the DNS provider
+    Applied,
+    /// The change was refused or couldn't be sent
+    Failed(ClientResponse),
+}
+
+impl From<UpdateOutcome> for ClientResponse {
+    fn from(value: UpdateOutcome) -> Self {
+        match value {
+            UpdateOutcome::Unchanged => ClientResponse::Ok(String::from(
+                "The record already points to this IP, no change was made.",
+            )),
+            UpdateOutcome::Applied => ClientResponse::Ok(String::from("Record updated.")),
+            UpdateOutcome::Failed(response) => response,
+        }
+    }
+}
+
+impl UpdateOutcome {
+    /// Merges the outcomes of the hostnames of an account, the first failure winning
+    pub fn combine(outcomes: impl IntoIterator<Item = UpdateOutcome>) -> UpdateOutcome {
+        let mut combined = UpdateOutcome::Unchanged;
+        for outcome in outcomes {
+            match outcome {
+                UpdateOutcome::Failed(_) => return outcome,
+                UpdateOutcome::Applied => combined = UpdateOutcome::Applied,
+                UpdateOutcome::Unchanged => {}
+            }
+        }
+
+        combined
+    }
+}
+
+/// Points the hostnames to the addresses, skipping the ones the cache shows no change for
+///
+/// The hostnames sharing a zone are written in a single change, with the TTL of the account.
+/// Applied and failed updates are written to the update history of each hostname.
+/// The outcomes are returned in the order of the hostnames.
+pub async fn update_hostnames(
+    attempt: &UpdateAttempt<'_>,
+    hostnames: &[FQDN],
+    addresses: Addresses,
+    dns: &Dns,
+    cache: &IpCache,
+    ttls: &TtlPolicy,
+    db: &DbConn,
+) -> Vec<(FQDN, UpdateOutcome)> {
+    let mut outcomes: Vec<(FQDN, UpdateOutcome)> = Vec::with_capacity(hostnames.len());
+    let mut changed: Vec<FQDN> = Vec::with_capacity(hostnames.len());
+
+    for hostname in hostnames {
+        let domain = hostname.to_string();
+        if cache.is_unchanged(&domain, &addresses) {
+            log::info!("{domain} already points to {addresses}, skipping update");
+            outcomes.push((hostname.clone(), UpdateOutcome::Unchanged));
+        } else {
+            changed.push(hostname.clone());
+        }
+    }
+
+    let account_ttl = account::ttl(attempt.domain, db).await.unwrap_or_else(|e| {
+        log::error!("Couldn't read the TTL of {}: {e}", attempt.domain);
+        None
+    });
+    let ttl = |zone: &FQDN| ttls.resolve(account_ttl, zone);
+
+    for (group, result) in dns
+        .upsert_address_records_batch(&changed, addresses, ttl)
+        .await
+    {
+        for hostname in group {
+            let domain = hostname.to_string();
+            let attempt = UpdateAttempt {
+                domain: &domain,
+                ..*attempt
+            };
+
+            let outcome = match &result {
+                Ok(change_id) => {
+                    let old = cache.get(&domain).map(|c| c.addresses);
+                    if let Err(e) = cache.store(&domain, addresses, db).await {
+                        log::error!("Couldn't save the last known IP of {domain}: {e}");
+                    }
+                    attempt.applied(db, old, addresses, change_id.clone()).await;
+                    UpdateOutcome::Applied
+                }
+                Err(response) => {
+                    attempt
+                        .rejected(db, Some(addresses), "The DNS provider refused the change")
+                        .await;
+                    UpdateOutcome::Failed(response.clone())
+                }
+            };
+            outcomes.push((hostname, outcome));
+        }
+    }
+
+    outcomes.sort_by_key(|(hostname, _)| hostnames.iter().position(|h| h == hostname));
+    outcomes
+}
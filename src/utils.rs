@@ -3,7 +3,7 @@ use std::sync::Mutex;
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use rand::{
     distributions::{Alphanumeric, DistString},
-    rngs::StdRng,
+    rngs::{OsRng, StdRng},
     SeedableRng,
 };
 use rocket::{fairing::AdHoc, serde::Deserialize};
@@ -15,6 +15,14 @@ pub fn compare_with_hash(password: &str, hash: &str) -> Result<(), argon2::passw
     password_hash.verify_password(algs, password)
 }
 
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
 pub fn generate_random_password(rng: &Mutex<StdRng>) -> (String, String) {
     let mut lock = rng.lock().unwrap();
     let password = Alphanumeric.sample_string(&mut *lock, 24);